@@ -18,6 +18,7 @@ struct Args {
 #[derive(Subcommand, Debug)]
 enum Command {
     PeerCount,
+    Peers,
     GetBal,
 }
 
@@ -27,6 +28,9 @@ impl Command {
             Self::PeerCount => core::node::rpc::GetPeerCountRequest
                 .into_rpc_request(id)
                 .unwrap(),
+            Self::Peers => core::node::rpc::GetPeersRequest
+                .into_rpc_request(id)
+                .unwrap(),
             // obviously, this should be changed later
             Self::GetBal => core::node::rpc::GetBalanceRequest {
                 address: "".to_string(),
@@ -66,7 +70,7 @@ async fn main() -> MainResult<()> {
             }
 
             if ready.is_readable() {
-                let mut buf = [0u8; 1024];
+                let mut buf = [0u8; 1024 * 16];
                 let n = stream.read(&mut buf).await.unwrap();
                 let res: socket::Response = serde_json::from_slice(&buf[..n]).unwrap();
 
@@ -74,10 +78,11 @@ async fn main() -> MainResult<()> {
             }
         }
 
-        println!("accepting input: \npeer-count | get-bal | exit");
+        println!("accepting input: \npeer-count | peers | get-bal | exit");
         stdin.read_line(&mut buf)?;
         let command = match buf.drain(..).collect::<String>().trim() {
             "peer-count" => Command::PeerCount,
+            "peers" => Command::Peers,
             "get-bal" => Command::GetBal,
             "exit" => panic!("exit"),
             _ => {
@@ -1,11 +1,16 @@
 use clap::Parser;
-use client::node::ClientNode;
-use core::blockchain::chain::BOOT_NODE_LOCAL_ADDR;
-use core::node::Node;
+use client::node::{ClientNode, ClientNodeConfig};
+use core::behaviour::{bidding::BidStrategyKind, receipt::Receipts};
+use core::node::{
+    config::{ConnectionArgs, NodeConfig},
+    transport::TransportKind,
+    Node,
+};
 use core::telemetry::TRACING;
 use libp2p::identity::Keypair;
 use libp2p::{Multiaddr, PeerId};
 use std::sync::LazyLock;
+use tokio::io::{AsyncBufReadExt, BufReader};
 use tracing;
 
 #[derive(Parser, Debug)]
@@ -15,8 +20,29 @@ struct Args {
     key: Option<String>,
     #[arg(short = 'a')]
     rpc_addr: Option<String>,
+    /// Address of the boot node, only dialed when no peer in the address book can be reached
     #[arg(short = 'd')]
     dial_addr: Option<String>,
+    /// Discover peers on the local network, the boot node is then only dialed if `-d` is passed
+    #[arg(long)]
+    mdns: bool,
+    /// Transports to dial on, may be passed more than once. Defaults to quic
+    #[arg(short = 't', long = "transport", value_enum)]
+    transports: Vec<TransportKind>,
+    /// Most paid per token, providers asking more are not considered
+    #[arg(long)]
+    max_price: Option<f64>,
+    /// Have providers commit to their bids & reveal them once bidding closes
+    #[arg(long)]
+    sealed_bids: bool,
+    /// How bids are ranked, auctions started over rpc may pick their own
+    #[arg(long, value_enum)]
+    bid_strategy: Option<BidStrategyKind>,
+    /// Times an auction is restarted before giving up
+    #[arg(long)]
+    auction_retries: Option<u32>,
+    #[command(flatten)]
+    connection: ConnectionArgs,
 }
 
 #[tokio::main]
@@ -35,23 +61,69 @@ async fn main() -> core::MainResult<()> {
     };
     let peer_id = PeerId::from_public_key(&keypair.public());
     tracing::warn!("id: {peer_id:#?}");
+    let mut config = NodeConfig {
+        mdns: args.mdns,
+        ..Default::default()
+    };
+    if !args.transports.is_empty() {
+        config.transports = args.transports;
+    }
+    args.connection.apply(&mut config);
+    let transports = config.transports.clone();
+    let mut client_config = ClientNodeConfig {
+        sealed_bids: args.sealed_bids,
+        ..Default::default()
+    };
+    if let Some(max) = args.max_price {
+        client_config.max_price_per_token = max;
+    }
+    if let Some(kind) = args.bid_strategy {
+        client_config.bid_strategy = kind.strategy();
+    }
+    if let Some(retries) = args.auction_retries {
+        client_config.auction_retries = retries;
+    }
+    client_config.receipts = Receipts::load(config.data_dir.as_deref())?;
+
     let mut node = Node::<ClientNode>::try_from_keys(
         keypair,
         args.rpc_addr.unwrap_or("127.0.0.1:0".to_string()),
+        config,
+        client_config,
     )
     .await
     .unwrap();
 
-    let boot_node_addr = args
-        .dial_addr
-        .unwrap_or(BOOT_NODE_LOCAL_ADDR.to_string())
-        .parse::<Multiaddr>()
-        .unwrap();
     let external_address = "/ip4/0.0.0.0/udp/0/quic-v1".parse::<Multiaddr>().unwrap();
 
     node.swarm.add_external_address(external_address);
 
-    node.swarm.dial(boot_node_addr).unwrap();
+    if args.mdns {
+        // mdns only finds peers we are listening alongside
+        for kind in transports.iter() {
+            node.listen_on(kind.default_listen_addr()).unwrap();
+        }
+    }
+
+    let boot_node_addr = match args.mdns && args.dial_addr.is_none() {
+        true => None,
+        false => Some(match args.dial_addr {
+            Some(addr) => addr.parse::<Multiaddr>().unwrap(),
+            None => transports[0].boot_node_local_addr(),
+        }),
+    };
+    node.bootstrap(boot_node_addr).unwrap();
+
+    // every line entered is a prompt to auction off
+    let prompts = node.inner.prompt_sender();
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(tokio::io::stdin()).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            if !line.trim().is_empty() && prompts.send(line).await.is_err() {
+                return;
+            }
+        }
+    });
 
     node.main_loop().await
 }
@@ -1,6 +1,11 @@
-use clap::{Parser, Subcommand};
-use core::telemetry::TRACING;
-use seraphic::{socket, RpcRequest};
+use clap::{Parser, Subcommand, ValueEnum};
+use client::rpc::checked_request;
+use core::{
+    behaviour::{bidding::BidStrategyKind, AuctionId},
+    telemetry::TRACING,
+    MODEL_ID_0,
+};
+use seraphic::socket;
 use std::sync::LazyLock;
 use tokio::io::{AsyncReadExt, AsyncWriteExt, Interest};
 use tokio::net::TcpStream;
@@ -17,15 +22,52 @@ struct Args {
 
 #[derive(Subcommand, Debug)]
 enum Command {
-    StartAuction,
+    StartAuction {
+        #[arg(short = 'm', default_value = MODEL_ID_0)]
+        model: String,
+        /// Ranks the auction's bids instead of the client's configured strategy
+        #[arg(short = 's', value_enum)]
+        strategy: Option<BidStrategyKind>,
+    },
+    /// How an auction went, the last one started when no id is given
+    Outcome {
+        auction: Option<AuctionId>,
+    },
+    /// Stops an auction, the last one started when no id is given
+    Cancel {
+        auction: Option<AuctionId>,
+    },
+    /// How paying for an auction is going, the last one started when no id is given
+    Settlement {
+        auction: Option<AuctionId>,
+    },
+    /// Providers that have advertised their capabilities, only those serving `model` if given
+    Providers {
+        #[arg(short = 'm')]
+        model: Option<String>,
+    },
+    Peers,
 }
 
 impl Command {
-    fn into_req(self, id: impl ToString) -> socket::Request {
+    fn into_req(self, id: impl ToString) -> core::MainResult<socket::Request> {
         match self {
-            Self::StartAuction => client::rpc::StartAuctionRequest
-                .into_rpc_request(id)
-                .unwrap(),
+            Self::StartAuction { model, strategy } => {
+                checked_request(&client::rpc::StartAuctionRequest { model, strategy }, id)
+            }
+            Self::Outcome { auction } => {
+                checked_request(&client::rpc::OutcomeRequest { auction }, id)
+            }
+            Self::Cancel { auction } => {
+                checked_request(&client::rpc::CancelRequest { auction }, id)
+            }
+            Self::Settlement { auction } => {
+                checked_request(&client::rpc::SettlementRequest { auction }, id)
+            }
+            Self::Providers { model } => {
+                checked_request(&client::rpc::ProvidersRequest { model }, id)
+            }
+            Self::Peers => checked_request(&core::node::rpc::GetPeersRequest, id),
         }
     }
 }
@@ -39,7 +81,7 @@ async fn main() -> core::MainResult<()> {
     let mut buf = String::new();
     let mut id = 1;
 
-    let mut req = args.command.into_req(id);
+    let mut req = args.command.into_req(id)?;
 
     let mut stream = TcpStream::connect(args.rpc_addr).await.unwrap();
 
@@ -59,7 +101,7 @@ async fn main() -> core::MainResult<()> {
             }
 
             if ready.is_readable() {
-                let mut buf = [0u8; 1024];
+                let mut buf = [0u8; 1024 * 16];
                 let n = stream.read(&mut buf).await.unwrap();
                 let res: socket::Response = serde_json::from_slice(&buf[..n]).unwrap();
 
@@ -67,17 +109,50 @@ async fn main() -> core::MainResult<()> {
             }
         }
 
-        println!("accepting input: \nauction | exit");
+        println!("accepting input: \nauction [strategy] | outcome [id] | cancel [id] | settlement [id] | providers [model] | peers | exit");
         stdin.read_line(&mut buf)?;
-        let command = match buf.drain(..).collect::<String>().trim() {
-            "auction" => Command::StartAuction,
-            "exit" => panic!("exit"),
+        let input = std::mem::take(&mut buf);
+        let command = match input.split_whitespace().collect::<Vec<_>>()[..] {
+            ["auction"] => Command::StartAuction {
+                model: MODEL_ID_0.to_string(),
+                strategy: None,
+            },
+            ["auction", kind] if BidStrategyKind::from_str(kind, true).is_ok() => {
+                Command::StartAuction {
+                    model: MODEL_ID_0.to_string(),
+                    strategy: BidStrategyKind::from_str(kind, true).ok(),
+                }
+            }
+            ["outcome"] => Command::Outcome { auction: None },
+            ["outcome", id] if id.parse::<AuctionId>().is_ok() => Command::Outcome {
+                auction: id.parse().ok(),
+            },
+            ["cancel"] => Command::Cancel { auction: None },
+            ["cancel", id] if id.parse::<AuctionId>().is_ok() => Command::Cancel {
+                auction: id.parse().ok(),
+            },
+            ["settlement"] => Command::Settlement { auction: None },
+            ["settlement", id] if id.parse::<AuctionId>().is_ok() => Command::Settlement {
+                auction: id.parse().ok(),
+            },
+            ["providers"] => Command::Providers { model: None },
+            ["providers", model] => Command::Providers {
+                model: Some(model.to_string()),
+            },
+            ["peers"] => Command::Peers,
+            ["exit"] => panic!("exit"),
             _ => {
-                tracing::warn!("{buf} is not a valid input");
+                tracing::warn!("{} is not a valid input", input.trim());
                 continue;
             }
         };
         id += 1;
-        req = command.into_req(id);
+        req = match command.into_req(id) {
+            Ok(req) => req,
+            Err(err) => {
+                tracing::warn!("can't send {}: {err}", input.trim());
+                continue;
+            }
+        };
     }
 }
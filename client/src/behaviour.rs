@@ -1,4 +1,7 @@
-use core::node::behaviour::{NodeBehaviourEvent, NodeNetworkBehaviour, SharedBehaviour};
+use core::node::{
+    behaviour::{NodeBehaviourEvent, NodeNetworkBehaviour, SharedBehaviour},
+    config::NodeConfig,
+};
 use libp2p::swarm::NetworkBehaviour;
 
 #[derive(NetworkBehaviour)]
@@ -21,11 +24,11 @@ impl AsMut<SharedBehaviour> for ClientNodeBehaviour {
 }
 
 impl NodeNetworkBehaviour for ClientNodeBehaviour {
-    fn new(keys: libp2p::identity::Keypair) -> Self
+    fn new(keys: libp2p::identity::Keypair, config: &NodeConfig) -> Self
     where
         Self: Sized,
     {
-        let shared = SharedBehaviour::new(keys.clone());
+        let shared = SharedBehaviour::new(keys.clone(), config);
         Self {
             shared,
             rendezvous: libp2p::rendezvous::client::Behaviour::new(keys),
@@ -1,84 +1,743 @@
 use crate::{
     behaviour::ClientNodeBehaviour,
-    rpc::{ClientRequestWrapper, StartAuctionResponse},
+    rpc::{
+        AuctionOutcome, CancelResponse, ClientNodeNamespace, ClientRequestWrapper, OutcomeResponse,
+        ProviderEntry, ProvidersResponse, Settlement, SettlementResponse, SettlementStatus,
+        StartAuctionResponse,
+    },
 };
 use core::{
     behaviour::{
+        auction::{AuctionMode, AuctionRequest, AuctionTerms, BidCommitment, BidMessage},
+        bidding::{BidStrategy, BidStrategyKind, CandidateBid, Reputation},
+        capability::{CapabilityAdvert, ProviderDirectory},
+        dht::ProviderRecords,
         gossip::NetworkTopic,
+        receipt::{Receipts, SignedReceipt},
+        rendezvous::{Discoverer, RoleNamespace},
         req_res::{NetworkRequest, NetworkResponse},
-        streaming::{connection_handler, StreamMessage},
-        ProvisionBid,
+        status::NodeRole,
+        streaming::{request_completion, Completion, InferenceParams, Progress, SessionOpen},
+        AuctionId, ProvisionBid,
+    },
+    blockchain::{
+        chain::{boot_node_peer_id, find_transfer},
+        transaction::transfer::Transfer,
     },
     node::{behaviour::NodeBehaviourEvent, Node, NodeType, NodeTypeEvent},
-    util::{heap::max::MaxHeap, OneOf},
-    MainResult,
+    util::{hash::Hash as _, now_millis, OneOf, PublicKeyBytes},
+    MainResult, MODEL_IDS, MODEL_ID_0,
 };
 use libp2p::{
-    futures::StreamExt,
-    gossipsub, request_response,
-    swarm::{NetworkBehaviour, SwarmEvent},
+    futures::{channel::oneshot, StreamExt},
+    gossipsub, kad, request_response,
+    request_response::OutboundRequestId,
+    swarm::{dial_opts::DialOpts, NetworkBehaviour, SwarmEvent},
     PeerId, Swarm,
 };
-use seraphic::{socket, RpcRequestWrapper};
-use serde_json::json;
-use std::time::Duration;
-use tokio::spawn;
+use seraphic::{socket, RpcNamespace, RpcRequestWrapper};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::{spawn, sync::mpsc};
 use tracing::warn;
 
 #[derive(Debug)]
 pub struct ClientNode {
-    state: ClientNodeState,
+    /// Every auction in progress, by the id of its current attempt
+    auctions: HashMap<AuctionId, ClientNodeState>,
+    max_auctions: usize,
+    discoverer: Discoverer,
+    provider_records: ProviderRecords,
+    /// Capabilities providers advertised, auctions no provider suits aren't published
+    directory: ProviderDirectory,
+    max_price_per_token: f64,
+    sealed_bids: bool,
+    /// Ranks bids in auctions that don't pick their own strategy
+    bid_strategy: Arc<dyn BidStrategy>,
+    reputation: Reputation,
+    /// Countersigned receipts of every session, what providers are paid by
+    receipts: Receipts,
+    /// Payments for sessions that ended with a receipt, by the id of the auction's first attempt
+    settlements: HashMap<AuctionId, Settlement>,
+    /// Transfers of `settlements` that aren't in a block yet, republished until they are
+    unsettled: HashMap<AuctionId, Transfer>,
+    check_settlements_at: Instant,
+    auction_retries: u32,
+    /// How every auction started so far went, by the id of its first attempt
+    outcomes: HashMap<AuctionId, AuctionOutcome>,
+    last_auction: Option<AuctionId>,
+    /// Last prompt the user entered, reused by auctions started over rpc
+    prompt: Option<String>,
+    prompt_sender: mpsc::Sender<String>,
+    prompts: mpsc::Receiver<String>,
+    session_sender: mpsc::Sender<SessionOutcome>,
+    sessions: mpsc::Receiver<SessionOutcome>,
 }
 type State = ClientNodeState;
 
+#[derive(Debug)]
+pub struct ClientNodeConfig {
+    /// Most we will pay per token, providers asking more don't bid
+    pub max_price_per_token: f64,
+    /// Run sealed auctions, providers can't see each other's bids until bidding closes
+    pub sealed_bids: bool,
+    /// Ranks bids, unless an auction is started with its own strategy
+    pub bid_strategy: Arc<dyn BidStrategy>,
+    /// Times an auction is restarted when it gets no acceptable bid or no provider can be
+    /// connected to
+    pub auction_retries: u32,
+    /// Most auctions & sessions in progress at once, counting retries
+    pub max_auctions: usize,
+    /// Receipts of earlier sessions, usually loaded from the data directory
+    pub receipts: Receipts,
+}
+
+impl Default for ClientNodeConfig {
+    fn default() -> Self {
+        Self {
+            max_price_per_token: 10.,
+            sealed_bids: false,
+            bid_strategy: BidStrategyKind::default().strategy(),
+            auction_retries: 2,
+            max_auctions: 32,
+            receipts: Receipts::default(),
+        }
+    }
+}
+
+/// Sent back to the node by a finished session task, with everything it received
+type SessionOutcome = (PeerId, AuctionId, Progress, MainResult<Completion>);
+
+/// What the user asked for, carried through every state of an auction
+#[derive(Debug, Clone)]
+struct InferenceRequest {
+    /// Reported to the user, stays the same across retries
+    id: AuctionId,
+    /// Current attempt, starting at 1
+    attempt: u32,
+    /// Fresh for every attempt, providers' bids would otherwise be dropped as duplicates
+    auction: AuctionId,
+    model: String,
+    prompt: String,
+    strategy: Arc<dyn BidStrategy>,
+    /// Output streamed by providers whose sessions died, the next one resumes after it
+    received: Progress,
+}
+
+/// Where a single auction is at, each runs independently of the others
 #[derive(Debug)]
 enum ClientNodeState {
-    Idle,
+    /// Waiting on the DHT to tell us which peers serve `model`
+    LookingUpProviders {
+        request: InferenceRequest,
+        query: kad::QueryId,
+    },
     Auctioning {
+        request: InferenceRequest,
+        terms: AuctionTerms,
         start: std::time::Instant,
-        bids: MaxHeap<ProvisionBid>,
+        bids: Vec<CandidateBid>,
+        /// Providers that have already bid, each may only bid once
+        bidders: HashSet<PeerId>,
+        /// Commitments to sealed bids that haven't been revealed yet
+        commitments: HashMap<PeerId, BidCommitment>,
     },
     AttemptingConnection {
+        request: InferenceRequest,
+        terms: AuctionTerms,
         bid: ProvisionBid,
         provider: PeerId,
+        /// Our request to open a stream, its response is matched to the auction with it
+        open_request: OutboundRequestId,
+        since: Instant,
+        /// Every other bid, tried in turn if the provider can't be connected to
+        runners_up: Vec<CandidateBid>,
     },
-    DirectlyConnected {
+    /// Streaming the completion from the winning provider
+    InSession {
+        request: InferenceRequest,
+        terms: AuctionTerms,
         provider: PeerId,
-        messages: Vec<StreamMessage>,
+        /// Bids to fail over to if the session dies
+        runners_up: Vec<CandidateBid>,
+        /// Stops the session, taken once it has been cancelled
+        cancel: Option<oneshot::Sender<()>>,
     },
+    /// Waiting to restart an auction that failed
+    Retrying {
+        request: InferenceRequest,
+        at: Instant,
+    },
+}
+impl ClientNodeState {
+    fn request(&self) -> &InferenceRequest {
+        match self {
+            Self::LookingUpProviders { request, .. }
+            | Self::Auctioning { request, .. }
+            | Self::AttemptingConnection { request, .. }
+            | Self::InSession { request, .. }
+            | Self::Retrying { request, .. } => request,
+        }
+    }
+
+    /// Whatever the auction is waiting for, if it's time for it
+    fn due(&self) -> Option<ClientNodeEvent> {
+        let auction = self.request().auction;
+        match self {
+            Self::LookingUpProviders { .. } | Self::InSession { .. } => None,
+            Self::Auctioning {
+                request,
+                terms,
+                start,
+                bids,
+                commitments,
+                ..
+            } => {
+                let now = now_millis();
+                let bidding = match terms.mode {
+                    AuctionMode::Open => start.elapsed() < AUCTIONING_DURATION,
+                    // Every bid has to be in before any is chosen
+                    AuctionMode::Sealed { reveal_deadline } => {
+                        now <= terms.deadline || (!commitments.is_empty() && now <= reveal_deadline)
+                    }
+                };
+                if bidding {
+                    return None;
+                }
+                // Keep waiting while none of the bids are acceptable
+                if let Some(chosen) = request.strategy.choose(bids, terms) {
+                    let bid = chosen.bid.clone();
+                    return Some(ClientNodeEvent::ChoseBid { auction, bid });
+                }
+                terms
+                    .closed(now)
+                    .then_some(ClientNodeEvent::NoAcceptableBids(auction))
+            }
+            Self::AttemptingConnection { since, .. } => (since.elapsed() >= CONNECT_TIMEOUT)
+                .then_some(ClientNodeEvent::ConnectionTimedOut(auction)),
+            Self::Retrying { at, .. } => {
+                (Instant::now() >= *at).then_some(ClientNodeEvent::RetryAuction(auction))
+            }
+        }
+    }
 }
+
 const AUCTIONING_DURATION: Duration = Duration::from_millis(100);
+/// How long after an auction is published providers may still bid in it
+const BID_DEADLINE: Duration = Duration::from_secs(10);
+/// How long providers may commit to bids in a sealed auction, no bid can be chosen sooner
+const SEALED_BIDDING: Duration = Duration::from_secs(3);
+/// How long after a sealed auction's bidding closes providers have to reveal their bids
+const REVEAL_WINDOW: Duration = Duration::from_secs(3);
+/// How long a provider has to accept the stream, the next best bid is tried after
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Pause before a failed auction is restarted
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+/// How often the chain is checked for pending settlements, which are republished if missing
+const SETTLEMENT_CHECK: Duration = Duration::from_secs(10);
 
 impl ClientNode {
-    fn start_auction(node: &mut Node<Self>) -> MainResult<()> {
-        // definately should be a struct later, but for now this is fine
-        let data = json!({
-        // amt of input tokens
-            "input_length": 50
-        });
+    /// Used to hand the node prompts typed by the user
+    pub fn prompt_sender(&self) -> mpsc::Sender<String> {
+        self.prompt_sender.clone()
+    }
+
+    /// Starts auctioning `prompt` off, returning the id its outcome is kept under.
+    /// Bids are ranked with the node's strategy unless `strategy` is given
+    fn start_auction(
+        node: &mut Node<Self>,
+        model: String,
+        prompt: String,
+        strategy: Option<Arc<dyn BidStrategy>>,
+    ) -> MainResult<AuctionId> {
+        if node.inner.auctions.len() >= node.inner.max_auctions {
+            return Err(format!(
+                "{} auctions are already in progress",
+                node.inner.auctions.len()
+            )
+            .into());
+        }
+        let id = AuctionId::random();
+        let request = InferenceRequest {
+            id,
+            attempt: 1,
+            auction: id,
+            model,
+            prompt,
+            strategy: strategy.unwrap_or_else(|| node.inner.bid_strategy.clone()),
+            received: Progress::default(),
+        };
+        node.inner.last_auction = Some(id);
+        node.inner
+            .outcomes
+            .insert(id, AuctionOutcome::Pending { attempt: 1 });
+        Self::look_up_providers(node, request);
+        Ok(id)
+    }
+
+    /// Looks up the providers of the model, the auction is published once the lookup finishes
+    fn look_up_providers(node: &mut Node<Self>, request: InferenceRequest) {
+        let query = node
+            .inner
+            .provider_records
+            .lookup(&mut node.swarm.behaviour_mut().shared.kad, &request.model);
+        let state = ClientNodeState::LookingUpProviders { request, query };
+        node.inner.insert(state);
+    }
 
-        node.swarm
+    fn insert(&mut self, state: ClientNodeState) {
+        self.auctions.insert(state.request().auction, state);
+    }
+
+    /// Removes the state of `auction` if it is `expected`, anything else is left in place
+    fn take(
+        &mut self,
+        auction: &AuctionId,
+        expected: impl FnOnce(&ClientNodeState) -> bool,
+    ) -> Option<ClientNodeState> {
+        match self.auctions.get(auction) {
+            Some(state) if expected(state) => self.auctions.remove(auction),
+            _ => None,
+        }
+    }
+
+    /// Stops the auction started as `id`, a running session is told to stop & its outcome is
+    /// recorded once the provider reports its usage
+    fn cancel(&mut self, id: AuctionId) -> MainResult<()> {
+        let auction = self
+            .auctions
+            .iter()
+            .find_map(|(auction, state)| (state.request().id == id).then_some(*auction))
+            .ok_or(format!("auction {id} isn't running"))?;
+        if let Some(State::InSession { cancel, .. }) = self.auctions.get_mut(&auction) {
+            let cancel = cancel
+                .take()
+                .ok_or("the session is already being cancelled")?;
+            // The session may have just ended, its outcome is then kept
+            let _ = cancel.send(());
+            return Ok(());
+        }
+        let request = self.auctions.remove(&auction).unwrap().request().clone();
+        tracing::info!("cancelled auction {id} on attempt {}", request.attempt);
+        let outcome = AuctionOutcome::Cancelled {
+            provider: None,
+            content: request.received.content,
+            usage: None,
+        };
+        self.outcomes.insert(id, outcome);
+        Ok(())
+    }
+
+    /// Pays the provider what `receipt` metered for the auction started as `id` out of our
+    /// unspent outputs, the transfer is broadcast to miners & tracked until it is mined.
+    /// The settlement is rejected if we can't cover it
+    fn settle(node: &mut Node<Self>, id: AuctionId, receipt: &SignedReceipt) -> MainResult<()> {
+        let amount = receipt.receipt.cost();
+        if amount <= 0. {
+            return Ok(());
+        }
+        let digest = receipt.receipt.digest()?;
+        let mut settlement = Settlement {
+            provider: receipt.receipt.provider,
+            transfer: None,
+            amount,
+            receipt: digest.clone(),
+            status: SettlementStatus::Pending,
+        };
+
+        // Outputs spent by payments that aren't in a block yet can't be spent again
+        let reserved: HashSet<&String> = node
+            .inner
+            .unsettled
+            .values()
+            .flat_map(|transfer| transfer.inputs())
+            .collect();
+        let me = PublicKeyBytes::from(node.keys().public());
+        let (mut inputs, mut spent) = (vec![], 0.);
+        for (output_id, output) in node.ledger().spendable(&me) {
+            if spent >= amount {
+                break;
+            }
+            if !reserved.contains(output_id) {
+                inputs.push(output_id.clone());
+                spent += output.amount();
+            }
+        }
+        if spent < amount {
+            let reason = format!("only {spent} of the {amount} owed can be spent");
+            settlement.status = SettlementStatus::Rejected {
+                reason: reason.clone(),
+            };
+            node.inner.settlements.insert(id, settlement);
+            return Err(reason.into());
+        }
+
+        let mut transfer = Transfer::new(
+            node.keys().public(),
+            receipt.provider_key().clone(),
+            amount,
+            inputs,
+            Some(digest),
+        )
+        .with_change(spent - amount);
+        transfer.sign(node.keys())?;
+        tracing::info!(
+            "paying {amount} for auction {id} in {}",
+            transfer.hash_ref()
+        );
+        settlement.transfer = Some(transfer.hash_ref().to_string());
+        node.inner.settlements.insert(id, settlement);
+        Self::publish_transfer(node, &transfer);
+        node.inner.unsettled.insert(id, transfer);
+        Ok(())
+    }
+
+    /// Broadcasts `transfer` to miners, it is published again at the next settlement check
+    /// until it is mined
+    fn publish_transfer(node: &mut Node<Self>, transfer: &Transfer) {
+        let published = node.swarm.behaviour_mut().shared.gossip.publish(
+            NetworkTopic::PendingTx.publish(),
+            serde_json::to_vec(transfer).expect("failed to serialize transfer"),
+        );
+        match published {
+            Ok(_) | Err(gossipsub::PublishError::Duplicate) => {}
+            Err(err) => {
+                tracing::debug!("failed to publish transfer {}: {err}", transfer.hash_ref())
+            }
+        }
+    }
+
+    /// Restarts the auction after `RETRY_DELAY` if it has attempts left, otherwise records
+    /// that it failed
+    fn retry_or_fail(node: &mut Node<Self>, request: InferenceRequest, reason: String) {
+        let id = request.id;
+        let outcome = match request.attempt > node.inner.auction_retries {
+            true => {
+                tracing::error!(
+                    "auction {id} failed after {} attempts: {reason}",
+                    request.attempt
+                );
+                AuctionOutcome::Failed {
+                    attempts: request.attempt,
+                    reason,
+                }
+            }
+            false => {
+                tracing::warn!("retrying auction {id}: {reason}");
+                let attempt = request.attempt + 1;
+                node.inner.insert(State::Retrying {
+                    request,
+                    at: Instant::now() + RETRY_DELAY,
+                });
+                AuctionOutcome::Pending { attempt }
+            }
+        };
+        node.inner.outcomes.insert(id, outcome);
+    }
+
+    /// Asks the provider that made `bid` to open a stream, the rest are kept to fall back on
+    fn attempt_connection(
+        node: &mut Node<Self>,
+        request: InferenceRequest,
+        terms: AuctionTerms,
+        bid: ProvisionBid,
+        runners_up: Vec<CandidateBid>,
+    ) {
+        let open_request = node
+            .swarm
             .behaviour_mut()
             .shared
-            .gossip
-            .publish(
-                NetworkTopic::Auction.publish(),
-                serde_json::to_vec(&data).unwrap(),
-            )
-            .expect("failed to publish auction start");
-        node.inner.state = ClientNodeState::Auctioning {
-            start: std::time::Instant::now(),
-            bids: vec![].into(),
+            .req_res
+            .send_request(&bid.peer, NetworkRequest::OpenStream);
+        node.inner.insert(State::AttemptingConnection {
+            request,
+            terms,
+            provider: bid.peer,
+            bid,
+            open_request,
+            since: Instant::now(),
+            runners_up,
+        });
+    }
+
+    /// The auction waiting on the response to `open_request`
+    fn opening(&self, open_request: OutboundRequestId) -> Option<AuctionId> {
+        self.auctions
+            .iter()
+            .find_map(|(auction, state)| match state {
+                State::AttemptingConnection {
+                    open_request: ours, ..
+                } => (*ours == open_request).then_some(*auction),
+                _ => None,
+            })
+    }
+
+    /// Tries the next best bid once the provider being connected to can't be,
+    /// restarting the auction when there is none
+    fn fall_back(node: &mut Node<Self>, auction: &AuctionId, reason: String) {
+        let Some(State::AttemptingConnection {
+            request,
+            terms,
+            provider,
+            runners_up,
+            ..
+        }) = node.inner.take(auction, |state| {
+            matches!(state, State::AttemptingConnection { .. })
+        })
+        else {
+            return;
+        };
+        tracing::warn!("could not connect to {provider}: {reason}");
+        Self::try_runner_up(node, request, terms, runners_up, reason);
+    }
+
+    /// Connects to the best of `runners_up` whose bid still holds, restarting the auction
+    /// when none is acceptable
+    fn try_runner_up(
+        node: &mut Node<Self>,
+        request: InferenceRequest,
+        terms: AuctionTerms,
+        mut runners_up: Vec<CandidateBid>,
+        reason: String,
+    ) {
+        let now = now_millis();
+        runners_up.retain(|candidate| !candidate.bid.expired(now));
+        let next = request
+            .strategy
+            .choose(&runners_up, &terms)
+            .map(|chosen| chosen.bid.peer)
+            .and_then(|peer| runners_up.iter().position(|c| c.bid.peer == peer));
+        match next {
+            Some(next) => {
+                let next = runners_up.remove(next);
+                Self::attempt_connection(node, request, terms, next.bid, runners_up);
+            }
+            None => Self::retry_or_fail(node, request, reason),
+        }
+    }
+
+    fn publish_auction(node: &mut Node<Self>, request: InferenceRequest) -> MainResult<()> {
+        let now = now_millis();
+        let (deadline, mode) = match node.inner.sealed_bids {
+            false => (now + BID_DEADLINE.as_millis() as u64, AuctionMode::Open),
+            true => {
+                let deadline = now + SEALED_BIDDING.as_millis() as u64;
+                let reveal_deadline = deadline + REVEAL_WINDOW.as_millis() as u64;
+                (deadline, AuctionMode::Sealed { reveal_deadline })
+            }
         };
+        let terms = AuctionTerms {
+            auction: request.auction,
+            model: request.model.clone(),
+            input_tokens: request.prompt.split_whitespace().count() as u64,
+            expected_output_tokens: InferenceParams::default().max_tokens as u64,
+            max_price_per_token: node.inner.max_price_per_token,
+            deadline,
+            mode,
+        };
+        if !node.inner.directory.anyone_suits(&terms, now) {
+            let reason = format!("no advertised provider can take on a {} job", terms.model);
+            Self::retry_or_fail(node, request, reason);
+            return Ok(());
+        }
+        let auction = AuctionRequest::new(terms.clone(), node.keys())?;
+
+        let published = node.swarm.behaviour_mut().shared.gossip.publish(
+            NetworkTopic::Auction.publish(),
+            serde_json::to_vec(&auction)?,
+        );
+        if let Err(err) = published {
+            Self::retry_or_fail(node, request, format!("failed to publish auction: {err}"));
+            return Ok(());
+        }
+        node.inner.insert(ClientNodeState::Auctioning {
+            request,
+            terms,
+            start: std::time::Instant::now(),
+            bids: vec![],
+            bidders: HashSet::new(),
+            commitments: HashMap::new(),
+        });
         Ok(())
     }
+
+    /// Takes a commitment to a sealed bid from `sender`
+    fn receive_commitment(node: &mut Node<Self>, sender: PeerId, commitment: BidCommitment) {
+        if commitment.peer != sender {
+            node.ban_peer(
+                sender,
+                format!("committed to a bid for {}", commitment.peer),
+            );
+            return;
+        }
+        let auction = commitment.auction;
+        let Some(State::Auctioning {
+            terms,
+            bidders,
+            commitments,
+            ..
+        }) = node.inner.auctions.get_mut(&auction)
+        else {
+            tracing::debug!("ignoring a late commitment from {sender} for auction {auction}");
+            return;
+        };
+        if !matches!(terms.mode, AuctionMode::Sealed { .. }) || terms.expired(now_millis()) {
+            tracing::debug!("ignoring a commitment from {sender}, auction {auction} takes none");
+            return;
+        }
+        if !bidders.insert(sender) {
+            node.ban_peer(sender, "bid more than once in an auction");
+            return;
+        }
+        commitments.insert(sender, commitment);
+    }
+
+    /// Checks a bid `sender` published on our topic, adding it to its auction if acceptable
+    fn receive_bid(node: &mut Node<Self>, sender: PeerId, message: BidMessage) {
+        let (signed, reveal) = match message {
+            BidMessage::Commit(commitment) => {
+                return Self::receive_commitment(node, sender, commitment)
+            }
+            BidMessage::Open(signed) => (signed, None),
+            BidMessage::Reveal(reveal) => (reveal.bid.clone(), Some(reveal)),
+        };
+        match signed.verify() {
+            Ok(signer) if signer == sender => {}
+            Ok(signer) => {
+                node.ban_peer(sender, format!("published a bid signed by {signer}"));
+                return;
+            }
+            Err(err) => {
+                node.ban_peer(sender, err.to_string());
+                return;
+            }
+        }
+        let bid = signed.bid;
+        tracing::warn!("received bid: {bid:#?}");
+        let now = now_millis();
+        if bid.expired(now) {
+            tracing::debug!("ignoring an expired bid from {}", bid.peer);
+            return;
+        }
+        let Some(auction) = bid.auction else {
+            tracing::debug!("ignoring a bid from {} for no auction", bid.peer);
+            return;
+        };
+        let Some(State::Auctioning {
+            request,
+            terms,
+            start,
+            bids,
+            bidders,
+            commitments,
+        }) = node.inner.auctions.get_mut(&auction)
+        else {
+            tracing::debug!(
+                "ignoring a late bid from {} for auction {auction}",
+                bid.peer
+            );
+            return;
+        };
+        match (terms.mode, reveal) {
+            (AuctionMode::Open, None) => {
+                if !bidders.insert(bid.peer) {
+                    node.ban_peer(sender, "bid more than once in an auction");
+                    return;
+                }
+            }
+            (AuctionMode::Sealed { .. }, Some(reveal)) => {
+                if !terms.expired(now) {
+                    tracing::debug!("ignoring a bid {sender} revealed before bidding closed");
+                    return;
+                }
+                // Each commitment is opened once, by the bid it was made to
+                match commitments.remove(&sender) {
+                    Some(commitment) if commitment.opened_by(&reveal) => {}
+                    Some(_) => {
+                        node.ban_peer(sender, "revealed a bid it didn't commit to");
+                        return;
+                    }
+                    None => {
+                        tracing::debug!("ignoring a bid {sender} never committed to");
+                        return;
+                    }
+                }
+            }
+            _ => {
+                tracing::debug!("ignoring a bid from {sender} that doesn't suit auction {auction}");
+                return;
+            }
+        }
+        if bid.bid > node.inner.max_price_per_token {
+            tracing::debug!("{} bid over our max price", bid.peer);
+            return;
+        }
+        // Only target providers known to serve the model, unless the DHT knows of none
+        let model = &request.model;
+        let providers = node.inner.provider_records.providers(model, Instant::now());
+        if !providers.is_empty() && !providers.contains(&bid.peer) {
+            tracing::warn!("{} is not a known provider of {model}", bid.peer);
+            return;
+        }
+        bids.push(CandidateBid {
+            latency: start.elapsed(),
+            reputation: node.inner.reputation.score(&bid.peer),
+            bid,
+        });
+    }
+
+    /// Dials every discovered provider we are not yet connected to & haven't banned
+    fn dial_discovered(node: &mut Node<Self>, discovered: Vec<(PeerId, Vec<libp2p::Multiaddr>)>) {
+        for (peer, addresses) in discovered {
+            if peer == *node.swarm.local_peer_id()
+                || node.swarm.is_connected(&peer)
+                || node.is_banned(&peer)
+            {
+                continue;
+            }
+            tracing::info!("dialing discovered provider {peer}");
+            // A dial from an earlier discovery may still be pending
+            if let Err(err) = node
+                .swarm
+                .dial(DialOpts::peer_id(peer).addresses(addresses).build())
+            {
+                warn!("failed to dial discovered provider {peer}: {err}");
+            }
+        }
+    }
 }
 
 #[derive(Debug)]
 pub enum ClientNodeEvent {
+    DiscoverProviders,
     UserInput(String),
-    ChoseBid(ProvisionBid),
-    GotCompletion { provider: PeerId, content: String },
+    ChoseBid {
+        auction: AuctionId,
+        bid: ProvisionBid,
+    },
+    /// Bidding closed without an acceptable bid
+    NoAcceptableBids(AuctionId),
+    ConnectionTimedOut(AuctionId),
+    RetryAuction(AuctionId),
+    /// Transfers paying for sessions are due to be looked for in the chain
+    CheckSettlements,
+    GotCompletion {
+        provider: PeerId,
+        auction: AuctionId,
+        content: String,
+        receipt: Box<SignedReceipt>,
+        cancelled: bool,
+    },
+    SessionFailed {
+        provider: PeerId,
+        auction: AuctionId,
+        reason: String,
+        /// Everything streamed before the session died, across every provider so far
+        received: Progress,
+    },
 }
 
 impl NodeTypeEvent for ClientNodeEvent {}
@@ -87,8 +746,13 @@ impl NodeType for ClientNode {
     type Behaviour = ClientNodeBehaviour;
     type Event = ClientNodeEvent;
     type RpcRequest = ClientRequestWrapper;
+    type Config = ClientNodeConfig;
+    const ROLE: NodeRole = NodeRole::Client;
 
-    fn init_with_swarm(_swarm: &mut Swarm<Self::Behaviour>) -> MainResult<Self>
+    fn init_with_swarm(
+        _swarm: &mut Swarm<Self::Behaviour>,
+        config: Self::Config,
+    ) -> MainResult<Self>
     where
         Self: Sized,
     {
@@ -99,28 +763,71 @@ impl NodeType for ClientNode {
             .gossip
             .subscribe(&NetworkTopic::from(&this_peer_id).subscribe())
             .expect("failed to subscribe to local topic");
+        _swarm
+            .behaviour_mut()
+            .shared
+            .gossip
+            .subscribe(&NetworkTopic::Capabilities.subscribe())
+            .expect("failed to subscribe to capabilities topic");
+        let (prompt_sender, prompts) = mpsc::channel(16);
+        let (session_sender, sessions) = mpsc::channel(16);
         Ok(Self {
-            state: ClientNodeState::Idle,
+            auctions: HashMap::new(),
+            max_auctions: config.max_auctions,
+            discoverer: Discoverer::new(MODEL_IDS.map(RoleNamespace::Providers)),
+            provider_records: ProviderRecords::default(),
+            directory: ProviderDirectory::default(),
+            max_price_per_token: config.max_price_per_token,
+            sealed_bids: config.sealed_bids,
+            bid_strategy: config.bid_strategy,
+            reputation: Reputation::default(),
+            receipts: config.receipts,
+            settlements: HashMap::new(),
+            unsettled: HashMap::new(),
+            check_settlements_at: Instant::now(),
+            auction_retries: config.auction_retries,
+            outcomes: HashMap::new(),
+            last_auction: None,
+            prompt: None,
+            prompt_sender,
+            prompts,
+            session_sender,
+            sessions,
         })
     }
 
     async fn next_event(&mut self) -> MainResult<Option<Self::Event>> {
-        match &mut self.state {
-            ClientNodeState::Idle => Ok(None),
-            ClientNodeState::Auctioning { start, bids } => {
-                let elapsed = start.elapsed();
-                tracing::warn!("elapsed: {elapsed:#?}");
-                if elapsed >= AUCTIONING_DURATION && bids.len() > 0 {
-                    if bids.peek().is_some() {
-                        let bid = bids.pop().expect("failed to get bid from heap");
-                        return Ok(Some(ClientNodeEvent::ChoseBid(bid)));
-                    }
-                }
-                Ok(None)
-            }
-            ClientNodeState::DirectlyConnected { provider, messages } => Ok(None),
-            ClientNodeState::AttemptingConnection { bid, provider } => Ok(None),
+        if self.discoverer.discovery_due() {
+            return Ok(Some(ClientNodeEvent::DiscoverProviders));
+        }
+        if let Ok((provider, auction, received, outcome)) = self.sessions.try_recv() {
+            return Ok(Some(match outcome {
+                Ok(Completion {
+                    content,
+                    receipt,
+                    cancelled,
+                }) => ClientNodeEvent::GotCompletion {
+                    provider,
+                    auction,
+                    content,
+                    receipt: Box::new(receipt),
+                    cancelled,
+                },
+                Err(err) => ClientNodeEvent::SessionFailed {
+                    provider,
+                    auction,
+                    reason: err.to_string(),
+                    received,
+                },
+            }));
+        }
+        if let Ok(prompt) = self.prompts.try_recv() {
+            return Ok(Some(ClientNodeEvent::UserInput(prompt)));
         }
+        if !self.unsettled.is_empty() && Instant::now() >= self.check_settlements_at {
+            return Ok(Some(ClientNodeEvent::CheckSettlements));
+        }
+        Ok(self.auctions.values().find_map(ClientNodeState::due))
     }
 
     async fn handle_self_event(node: &mut Node<Self>, e: Self::Event) -> MainResult<()>
@@ -128,30 +835,170 @@ impl NodeType for ClientNode {
         Self: Sized,
     {
         tracing::warn!("client event: {e:#?}");
-        match (e, &node.inner.state) {
-            (ClientNodeEvent::ChoseBid(bid), ClientNodeState::Auctioning { .. }) => {
-                node.swarm
-                    .behaviour_mut()
-                    .shared
-                    .req_res
-                    .send_request(&bid.peer, NetworkRequest::OpenStream);
-
-                // maybe there is no need for attempting connection?
-                node.inner.state = ClientNodeState::AttemptingConnection {
-                    provider: bid.peer,
-                    bid,
+        match e {
+            ClientNodeEvent::DiscoverProviders => {
+                node.inner.discoverer.discover(
+                    &mut node.swarm.behaviour_mut().rendezvous,
+                    boot_node_peer_id(),
+                );
+            }
+            ClientNodeEvent::UserInput(prompt) => {
+                node.inner.prompt = Some(prompt.clone());
+                if let Err(err) = Self::start_auction(node, MODEL_ID_0.to_string(), prompt, None) {
+                    tracing::warn!("prompt will be used for the next auction: {err}");
                 }
-                // if !node.swarm.is_connected(&bid.peer) {
-                //     node.swarm
-                //         .dial(bid.peer)
-                //         .expect("client failed to dial peer");
-                // }
-                //
-                // let control = node.swarm.behaviour_mut().shared.stream.new_control();
-                //
-                // node.swarm.dial
             }
-            _ => {}
+            ClientNodeEvent::ChoseBid { auction, bid } => {
+                let Some(State::Auctioning {
+                    request,
+                    terms,
+                    bids,
+                    ..
+                }) = node
+                    .inner
+                    .take(&auction, |state| matches!(state, State::Auctioning { .. }))
+                else {
+                    return Ok(());
+                };
+                let runners_up = bids
+                    .into_iter()
+                    .filter(|candidate| candidate.bid.peer != bid.peer)
+                    .collect();
+                Self::attempt_connection(node, request, terms, bid, runners_up);
+            }
+            ClientNodeEvent::NoAcceptableBids(auction) => {
+                if let Some(State::Auctioning { request, .. }) = node
+                    .inner
+                    .take(&auction, |state| matches!(state, State::Auctioning { .. }))
+                {
+                    Self::retry_or_fail(node, request, "no acceptable bids".to_string());
+                }
+            }
+            ClientNodeEvent::ConnectionTimedOut(auction) => {
+                Self::fall_back(node, &auction, "timed out".to_string());
+            }
+            ClientNodeEvent::RetryAuction(auction) => {
+                if let Some(State::Retrying { mut request, .. }) = node
+                    .inner
+                    .take(&auction, |state| matches!(state, State::Retrying { .. }))
+                {
+                    request.attempt += 1;
+                    request.auction = AuctionId::random();
+                    Self::look_up_providers(node, request);
+                }
+            }
+            ClientNodeEvent::CheckSettlements => {
+                node.inner.check_settlements_at = Instant::now() + SETTLEMENT_CHECK;
+                for (id, transfer) in std::mem::take(&mut node.inner.unsettled) {
+                    let Some(block) = find_transfer(node.blockchain(), transfer.hash_ref()) else {
+                        // Its inputs were spent by something else, it can never be mined
+                        if let Err(err) = node.ledger().check(&transfer) {
+                            tracing::error!("payment for auction {id} can't be mined: {err}");
+                            if let Some(settlement) = node.inner.settlements.get_mut(&id) {
+                                settlement.status = SettlementStatus::Rejected {
+                                    reason: err.to_string(),
+                                };
+                            }
+                            continue;
+                        }
+                        Self::publish_transfer(node, &transfer);
+                        node.inner.unsettled.insert(id, transfer);
+                        continue;
+                    };
+                    tracing::info!("auction {id} was settled in block {block}");
+                    if let Some(settlement) = node.inner.settlements.get_mut(&id) {
+                        settlement.status = SettlementStatus::Settled { block };
+                    }
+                }
+            }
+            ClientNodeEvent::GotCompletion {
+                provider,
+                auction,
+                content,
+                receipt,
+                cancelled,
+            } => {
+                let in_session = |state: &State| matches!(state, State::InSession { provider: current, .. } if *current == provider);
+                let Some(State::InSession { request, .. }) = node.inner.take(&auction, in_session)
+                else {
+                    return Ok(());
+                };
+                let usage = receipt.receipt.usage;
+                tracing::info!(
+                    "{provider} billed auction {auction} {} for {usage:?}",
+                    receipt.receipt.cost()
+                );
+                if let Err(err) = Self::settle(node, request.id, &receipt) {
+                    tracing::error!("failed to pay for auction {auction}: {err}");
+                }
+                if let Err(err) = node.inner.receipts.insert(*receipt) {
+                    tracing::error!("failed to keep the receipt of auction {auction}: {err}");
+                }
+                if cancelled {
+                    tracing::info!(
+                        "{provider} stopped auction {auction} after {} output tokens",
+                        usage.output_tokens
+                    );
+                    let outcome = AuctionOutcome::Cancelled {
+                        provider: Some(provider),
+                        content,
+                        usage: Some(usage),
+                    };
+                    node.inner.outcomes.insert(request.id, outcome);
+                    return Ok(());
+                }
+                tracing::info!(
+                    "{provider} completed auction {auction} with {} output tokens: {content}",
+                    usage.output_tokens
+                );
+                node.inner.reputation.record(provider, true);
+                node.inner.outcomes.insert(
+                    request.id,
+                    AuctionOutcome::Completed {
+                        provider,
+                        content,
+                        usage,
+                    },
+                );
+            }
+            ClientNodeEvent::SessionFailed {
+                provider,
+                auction,
+                reason,
+                received,
+            } => {
+                let in_session = |state: &State| matches!(state, State::InSession { provider: current, .. } if *current == provider);
+                let Some(State::InSession {
+                    mut request,
+                    terms,
+                    runners_up,
+                    cancel,
+                    ..
+                }) = node.inner.take(&auction, in_session)
+                else {
+                    return Ok(());
+                };
+                // Nobody takes over a session the user gave up on
+                if cancel.is_none() {
+                    tracing::warn!("cancelled session with {provider} ended badly: {reason}");
+                    let outcome = AuctionOutcome::Cancelled {
+                        provider: Some(provider),
+                        content: received.content,
+                        usage: None,
+                    };
+                    node.inner.outcomes.insert(request.id, outcome);
+                    return Ok(());
+                }
+                tracing::error!(
+                    "session with {provider} for auction {auction} failed after {} output tokens: \
+                     {reason}",
+                    received.output_tokens
+                );
+                node.inner.reputation.record(provider, false);
+                // Whoever takes over resumes from what was streamed so far
+                request.received = received;
+                Self::try_runner_up(node, request, terms, runners_up, reason);
+            }
         }
         Ok(())
     }
@@ -163,60 +1010,212 @@ impl NodeType for ClientNode {
     where
         Self: Sized,
     {
-        match (_e, &mut node.inner.state) {
-            (SwarmEvent::NewListenAddr { address, .. }, State::DirectlyConnected { .. }) => {
-                let listen_address = address.with_p2p(*node.swarm.local_peer_id()).unwrap();
+        if let SwarmEvent::ConnectionEstablished { peer_id, .. } = &_e {
+            if *peer_id == boot_node_peer_id() {
+                node.inner
+                    .discoverer
+                    .discover(&mut node.swarm.behaviour_mut().rendezvous, *peer_id);
+            }
+        }
+
+        // Still passed on so the node forgets the peer too
+        if let SwarmEvent::ConnectionClosed {
+            peer_id,
+            num_established: 0,
+            ..
+        } = &_e
+        {
+            node.inner.provider_records.forget(peer_id);
+        }
+
+        // Still passed on so the address is advertised
+        if let SwarmEvent::NewListenAddr { address, .. } = &_e {
+            let in_session = node
+                .inner
+                .auctions
+                .values()
+                .any(|state| matches!(state, State::InSession { .. }));
+            if let (true, Ok(listen_address)) = (
+                in_session,
+                address.clone().with_p2p(*node.swarm.local_peer_id()),
+            ) {
                 tracing::info!(%listen_address);
+            }
+        }
+
+        match _e {
+            SwarmEvent::Behaviour(NodeBehaviourEvent::RendezvousClient(event)) => {
+                let discovered = node.inner.discoverer.handle_event(event);
+                Self::dial_discovered(node, discovered);
                 Ok(None)
             }
-            (
-                SwarmEvent::Behaviour(NodeBehaviourEvent::Gossip(gossipsub::Event::Message {
-                    message: gossipsub::Message { topic, data, .. },
-                    ..
-                })),
-                State::Auctioning { ref mut bids, .. },
-            ) if topic == NetworkTopic::from(node.swarm.local_peer_id()).publish() => {
-                let bid: ProvisionBid =
-                    serde_json::from_slice(&data).expect("failed to serialize bid data");
-                tracing::warn!("received bid: {bid:#?}");
-                bids.insert(bid);
+            SwarmEvent::Behaviour(NodeBehaviourEvent::Kad(event)) => {
+                let Some((id, _)) = node.inner.provider_records.handle_event(&event) else {
+                    return Ok(Some(SwarmEvent::Behaviour(NodeBehaviourEvent::Kad(event))));
+                };
+                let looked_up = node.inner.auctions.iter().find_map(|(auction, state)| {
+                    matches!(state, State::LookingUpProviders { query, .. } if *query == id)
+                        .then_some(*auction)
+                });
+                let Some(auction) = looked_up else {
+                    return Ok(Some(SwarmEvent::Behaviour(NodeBehaviourEvent::Kad(event))));
+                };
+                if let Some(State::LookingUpProviders { request, .. }) =
+                    node.inner.auctions.remove(&auction)
+                {
+                    Self::publish_auction(node, request)?;
+                }
                 Ok(None)
             }
-            (
-                SwarmEvent::Behaviour(NodeBehaviourEvent::ReqRes(
-                    request_response::Event::Message {
-                        peer,
-                        message:
-                            request_response::Message::Response {
-                                request_id,
-                                response: NetworkResponse::OpenStreamAck { opened },
-                            },
+            SwarmEvent::Behaviour(NodeBehaviourEvent::Gossip(gossipsub::Event::Message {
+                propagation_source,
+                message:
+                    gossipsub::Message {
+                        topic,
+                        data,
+                        source,
+                        ..
                     },
-                )),
-                State::AttemptingConnection { bid, provider },
-            ) => {
+                ..
+            })) if topic == NetworkTopic::from(node.swarm.local_peer_id()).publish() => {
+                // Gossip is signed, a message without a source can't be tied to a provider
+                let Some(sender) = source else {
+                    tracing::debug!("ignoring an unsourced bid relayed by {propagation_source}");
+                    return Ok(None);
+                };
+                match serde_json::from_slice(&data) {
+                    Ok(message) => Self::receive_bid(node, sender, message),
+                    Err(err) => node.ban_peer(sender, format!("sent an undecodable bid: {err}")),
+                }
+                Ok(None)
+            }
+            SwarmEvent::Behaviour(NodeBehaviourEvent::Gossip(gossipsub::Event::Message {
+                propagation_source,
+                message:
+                    gossipsub::Message {
+                        topic,
+                        data,
+                        source,
+                        ..
+                    },
+                ..
+            })) if topic == NetworkTopic::Capabilities.publish() => {
+                let sender = source.unwrap_or(propagation_source);
+                let advert: CapabilityAdvert = match serde_json::from_slice(&data) {
+                    Ok(advert) => advert,
+                    Err(err) => {
+                        node.ban_peer(sender, format!("sent an undecodable advert: {err}"));
+                        return Ok(None);
+                    }
+                };
+                match advert.verify() {
+                    Ok(signer) if signer == sender => {
+                        let now = now_millis();
+                        node.inner
+                            .directory
+                            .insert(signer, advert.capabilities, now);
+                    }
+                    Ok(signer) => {
+                        node.ban_peer(sender, format!("published an advert signed by {signer}"))
+                    }
+                    Err(err) => node.ban_peer(sender, err.to_string()),
+                }
+                Ok(None)
+            }
+            SwarmEvent::Behaviour(NodeBehaviourEvent::ReqRes(
+                request_response::Event::Message {
+                    peer,
+                    message:
+                        request_response::Message::Response {
+                            request_id,
+                            response: NetworkResponse::OpenStreamAck { opened },
+                        },
+                },
+            )) => {
+                let Some(auction) = node.inner.opening(request_id) else {
+                    tracing::debug!("{peer} answered a stream request we gave up on");
+                    return Ok(None);
+                };
                 if !opened {
-                    tracing::error!("provider is busy, could not connect. Returning to idle state");
-                    node.inner.state = State::Idle;
+                    Self::fall_back(node, &auction, "provider is busy".to_string());
                     return Ok(None);
                 }
+                let Some(State::AttemptingConnection {
+                    request,
+                    terms,
+                    bid,
+                    provider,
+                    runners_up,
+                    ..
+                }) = node.inner.take(&auction, |state| {
+                    matches!(state, State::AttemptingConnection { .. })
+                })
+                else {
+                    tracing::debug!(
+                        "{peer} accepted a stream for auction {auction} after it moved on"
+                    );
+                    return Ok(None);
+                };
 
-                if !node.swarm.is_connected(provider) {
-                    node.swarm.dial(*provider)?;
+                if !node.swarm.is_connected(&provider) {
+                    // The state was taken above, so this falls back like `fall_back` would
+                    if let Err(err) = node.swarm.dial(provider) {
+                        warn!("could not connect to {provider}: {err}");
+                        Self::try_runner_up(node, request, terms, runners_up, err.to_string());
+                        return Ok(None);
+                    }
                 }
 
-                tokio::spawn(connection_handler(
-                    *provider,
-                    node.swarm.behaviour().shared.stream.new_control(),
-                ));
-
-                node.inner.state = State::DirectlyConnected {
-                    provider: *provider,
-                    messages: vec![],
+                let mut received = request.received.clone();
+                let mut params = InferenceParams::default();
+                params.max_tokens = params
+                    .max_tokens
+                    .saturating_sub(received.output_tokens as u32);
+                let open = SessionOpen {
+                    auction: request.auction,
+                    model: request.model.clone(),
+                    params,
+                    price_per_token: bid.bid,
+                    resume: (!received.content.is_empty()).then(|| received.content.clone()),
                 };
+                let control = node.swarm.behaviour().shared.stream.new_control();
+                let sessions = node.inner.session_sender.clone();
+                let prompt = request.prompt.clone();
+                let keys = node.keys().clone();
+                let (cancel, cancelled) = oneshot::channel();
+                tokio::spawn(async move {
+                    let outcome = request_completion(
+                        control,
+                        keys,
+                        provider,
+                        open,
+                        prompt,
+                        &mut received,
+                        cancelled,
+                    )
+                    .await;
+                    let _ = sessions.send((provider, auction, received, outcome)).await;
+                });
+
+                node.inner.insert(State::InSession {
+                    request,
+                    terms,
+                    provider,
+                    runners_up,
+                    cancel: Some(cancel),
+                });
                 Ok(None)
             }
-            (event, _state) => Ok(Some(event)),
+            SwarmEvent::Behaviour(NodeBehaviourEvent::ReqRes(
+                request_response::Event::OutboundFailure {
+                    request_id, error, ..
+                },
+            )) if node.inner.opening(request_id).is_some() => {
+                let auction = node.inner.opening(request_id).unwrap();
+                Self::fall_back(node, &auction, error.to_string());
+                Ok(None)
+            }
+            event => Ok(Some(event)),
         }
     }
 
@@ -227,6 +1226,14 @@ impl NodeType for ClientNode {
     where
         Self: Sized,
     {
+        // seraphic panics when parsing a method from a namespace it doesn't know of
+        let ours = _r
+            .method
+            .split_once('_')
+            .is_some_and(|(ns, _)| ClientNodeNamespace::try_from_str(ns).is_some());
+        if !ours {
+            return Ok(OneOf::Left(_r));
+        }
         let req = match Self::RpcRequest::try_from_rpc_req(_r.clone()) {
             Err(_) => return Ok(OneOf::Left(_r)),
             Ok(req) => req,
@@ -235,13 +1242,75 @@ impl NodeType for ClientNode {
         match req {
             ClientRequestWrapper::StartAuction(req) => {
                 warn!("client handling StartAuction");
-                let start = ClientNode::start_auction(_node);
+                let start = match _node.inner.prompt.clone() {
+                    Some(prompt) => ClientNode::start_auction(
+                        _node,
+                        req.model,
+                        prompt,
+                        req.strategy.map(BidStrategyKind::strategy),
+                    ),
+                    None => Err("no prompt has been entered yet".into()),
+                };
+                if let Err(err) = &start {
+                    warn!("could not start auction: {err}");
+                }
                 let response = StartAuctionResponse {
                     started: start.is_ok(),
+                    auction: start.ok(),
                 };
                 let json = serde_json::to_value(response)?;
                 return Ok(OneOf::Right(Ok(json)));
             }
+            ClientRequestWrapper::Outcome(req) => {
+                let auction = req.auction.or(_node.inner.last_auction);
+                let response = OutcomeResponse {
+                    auction,
+                    outcome: auction.and_then(|id| _node.inner.outcomes.get(&id).cloned()),
+                };
+                let json = serde_json::to_value(response)?;
+                Ok(OneOf::Right(Ok(json)))
+            }
+            ClientRequestWrapper::Providers(req) => {
+                let now = now_millis();
+                let providers = _node
+                    .inner
+                    .directory
+                    .providers(now)
+                    .filter(|(_, advert)| {
+                        req.model.as_ref().is_none_or(|m| advert.models.contains(m))
+                    })
+                    .map(|(peer, advert)| ProviderEntry {
+                        peer: *peer,
+                        capabilities: advert.clone(),
+                    })
+                    .collect();
+                let json = serde_json::to_value(ProvidersResponse { providers })?;
+                Ok(OneOf::Right(Ok(json)))
+            }
+            ClientRequestWrapper::Cancel(req) => {
+                let auction = req.auction.or(_node.inner.last_auction);
+                let cancelled = auction
+                    .ok_or_else(|| "no auction has been started".into())
+                    .and_then(|id| _node.inner.cancel(id));
+                if let Err(err) = &cancelled {
+                    warn!("could not cancel auction: {err}");
+                }
+                let response = CancelResponse {
+                    auction,
+                    cancelled: cancelled.is_ok(),
+                };
+                let json = serde_json::to_value(response)?;
+                Ok(OneOf::Right(Ok(json)))
+            }
+            ClientRequestWrapper::Settlement(req) => {
+                let auction = req.auction.or(_node.inner.last_auction);
+                let response = SettlementResponse {
+                    auction,
+                    settlement: auction.and_then(|id| _node.inner.settlements.get(&id).cloned()),
+                };
+                let json = serde_json::to_value(response)?;
+                Ok(OneOf::Right(Ok(json)))
+            }
         }
     }
 }
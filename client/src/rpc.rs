@@ -1,4 +1,29 @@
-use seraphic::{RpcNamespace, RpcRequest, RpcRequestWrapper};
+use core::behaviour::{
+    bidding::BidStrategyKind, capability::Capabilities, streaming::Usage, AuctionId,
+};
+use core::MainResult;
+use libp2p::PeerId;
+use seraphic::{socket, RpcNamespace, RpcRequest, RpcRequestWrapper};
+
+/// Longest request the node reads, seraphic drops longer ones without an answer
+pub const MAX_REQUEST_LEN: usize = std::mem::size_of::<socket::Request>();
+
+/// `request` as an rpc request, errors if it's too long for the node to read
+pub fn checked_request(
+    request: &impl RpcRequest,
+    id: impl ToString,
+) -> MainResult<socket::Request> {
+    let request = request.into_rpc_request(id)?;
+    let len = serde_json::to_vec(&request)?.len();
+    if len > MAX_REQUEST_LEN {
+        return Err(format!(
+            "{} request is {len} bytes, the node reads at most {MAX_REQUEST_LEN}",
+            request.method
+        )
+        .into());
+    }
+    Ok(request)
+}
 
 #[derive(RpcNamespace, Copy, Clone, PartialEq, Eq)]
 pub enum ClientNodeNamespace {
@@ -8,13 +33,160 @@ pub enum ClientNodeNamespace {
 #[derive(RpcRequestWrapper, Debug)]
 pub enum ClientRequestWrapper {
     StartAuction(StartAuctionRequest),
+    Outcome(OutcomeRequest),
+    Providers(ProvidersRequest),
+    Cancel(CancelRequest),
+    Settlement(SettlementRequest),
 }
 
 #[derive(RpcRequest, Debug, Clone, serde::Serialize, serde::Deserialize)]
 #[rpc_request(namespace = "ClientNodeNamespace:client")]
-pub struct StartAuctionRequest;
+pub struct StartAuctionRequest {
+    /// Long model ids make the request too long to send, see `checked_request`
+    pub model: String,
+    /// Ranks the auction's bids instead of the node's configured strategy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strategy: Option<BidStrategyKind>,
+}
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct StartAuctionResponse {
     pub started: bool,
+    /// Stays the same when the auction is retried, used to ask for its outcome
+    pub auction: Option<AuctionId>,
+}
+
+/// How an auction started over rpc went
+#[derive(RpcRequest, Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[rpc_request(namespace = "ClientNodeNamespace:client")]
+pub struct OutcomeRequest {
+    /// Defaults to the last auction started
+    pub auction: Option<AuctionId>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct OutcomeResponse {
+    pub auction: Option<AuctionId>,
+    /// None if no such auction was started
+    pub outcome: Option<AuctionOutcome>,
+}
+
+/// Stops an auction started over rpc, a running session is cut short
+#[derive(RpcRequest, Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[rpc_request(namespace = "ClientNodeNamespace:client")]
+pub struct CancelRequest {
+    /// Defaults to the last auction started
+    pub auction: Option<AuctionId>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CancelResponse {
+    pub auction: Option<AuctionId>,
+    /// False if the auction had already ended
+    pub cancelled: bool,
+}
+
+/// How the payment for an auction started over rpc is going
+#[derive(RpcRequest, Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[rpc_request(namespace = "ClientNodeNamespace:client")]
+pub struct SettlementRequest {
+    /// Defaults to the last auction started
+    pub auction: Option<AuctionId>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SettlementResponse {
+    pub auction: Option<AuctionId>,
+    /// None until the auction's session has ended with a receipt
+    pub settlement: Option<Settlement>,
+}
+
+/// Payment to the provider of a session, for what its receipt metered
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Settlement {
+    pub provider: PeerId,
+    /// Hash of the transfer paying the provider, None if it couldn't be paid for
+    pub transfer: Option<String>,
+    pub amount: f64,
+    /// Digest of the receipt the transfer references
+    pub receipt: String,
+    pub status: SettlementStatus,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case", tag = "status")]
+pub enum SettlementStatus {
+    /// Broadcast to miners, republished until it appears in a block
+    Pending,
+    Settled {
+        block: u64,
+    },
+    /// The client didn't have the funds, or they were spent before the transfer was mined
+    Rejected {
+        reason: String,
+    },
+}
+
+/// Providers whose capability adverts haven't expired
+#[derive(RpcRequest, Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[rpc_request(namespace = "ClientNodeNamespace:client")]
+pub struct ProvidersRequest {
+    /// Only providers serving this model, every provider when None
+    pub model: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ProvidersResponse {
+    pub providers: Vec<ProviderEntry>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ProviderEntry {
+    pub peer: PeerId,
+    pub capabilities: Capabilities,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case", tag = "status")]
+pub enum AuctionOutcome {
+    /// Still running, attempts are counted from 1
+    Pending { attempt: u32 },
+    Completed {
+        provider: PeerId,
+        content: String,
+        usage: Usage,
+    },
+    /// Every attempt failed, `reason` is why the last one did
+    Failed { attempts: u32, reason: String },
+    /// Stopped over rpc, `usage` is what the provider reported producing if a session was
+    /// running
+    Cancelled {
+        provider: Option<PeerId>,
+        content: String,
+        usage: Option<Usage>,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::MODEL_IDS;
+
+    #[test]
+    fn start_auction_requests_fit_or_error() {
+        for model in MODEL_IDS {
+            let request = StartAuctionRequest {
+                model: model.to_string(),
+                strategy: Some(BidStrategyKind::Cheapest),
+            };
+            assert!(checked_request(&request, 1000).is_ok());
+        }
+
+        let request = StartAuctionRequest {
+            model: "a-model-id-far-too-long-to-send".to_string(),
+            strategy: None,
+        };
+        let err = checked_request(&request, 1).unwrap_err();
+        assert!(err.to_string().contains("the node reads at most"));
+    }
 }
@@ -1,4 +1,7 @@
-use core::node::behaviour::{NodeBehaviourEvent, NodeNetworkBehaviour, SharedBehaviour};
+use core::node::{
+    behaviour::{NodeBehaviourEvent, NodeNetworkBehaviour, SharedBehaviour},
+    config::NodeConfig,
+};
 use libp2p::{identity::Keypair, swarm::NetworkBehaviour};
 
 #[derive(NetworkBehaviour)]
@@ -6,6 +9,8 @@ use libp2p::{identity::Keypair, swarm::NetworkBehaviour};
 pub struct ServerNodeBehaviour {
     pub shared: SharedBehaviour,
     pub rendezvous: libp2p::rendezvous::server::Behaviour,
+    /// Used to register at the boot node's rendezvous server
+    pub rendezvous_client: libp2p::rendezvous::client::Behaviour,
 }
 
 impl AsRef<SharedBehaviour> for ServerNodeBehaviour {
@@ -21,16 +26,17 @@ impl AsMut<SharedBehaviour> for ServerNodeBehaviour {
 }
 
 impl NodeNetworkBehaviour for ServerNodeBehaviour {
-    fn new(keys: Keypair) -> Self
+    fn new(keys: Keypair, config: &NodeConfig) -> Self
     where
         Self: Sized,
     {
-        let shared = SharedBehaviour::new(keys);
+        let shared = SharedBehaviour::new(keys.clone(), config);
         Self {
             shared,
             rendezvous: libp2p::rendezvous::server::Behaviour::new(
                 libp2p::rendezvous::server::Config::default(),
             ),
+            rendezvous_client: libp2p::rendezvous::client::Behaviour::new(keys),
         }
     }
 }
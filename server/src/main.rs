@@ -2,12 +2,26 @@ pub mod behaviour;
 pub mod node;
 
 use clap::{Parser, Subcommand};
-use core::blockchain::chain::{BOOT_NODE_KEYPAIR, BOOT_NODE_LISTEN_ADDR};
-use core::node::Node;
+use core::behaviour::pricing::PricingPolicy;
+use core::behaviour::receipt::Receipts;
+use core::blockchain::chain::BOOT_NODE_KEYPAIR;
+use core::inference::subprocess::SubprocessBackend;
+use core::node::{
+    config::{ConnectionArgs, NodeConfig},
+    transport::TransportKind,
+    Node,
+};
 use core::telemetry::TRACING;
 use libp2p::identity::Keypair;
-use node::{miner::MinerNode, provider::ProviderNode};
-use std::sync::LazyLock;
+use libp2p::Multiaddr;
+use node::{
+    miner::MinerNode,
+    provider::{ProviderNode, ProviderNodeConfig},
+};
+use std::{
+    sync::{Arc, LazyLock},
+    time::Duration,
+};
 use tracing::warn;
 
 // https://github.com/libp2p/rust-libp2p/tree/master/examples/rendezvous
@@ -21,15 +35,52 @@ struct Args {
     boot: bool,
     #[arg(short = 'k')]
     key: Option<String>,
+    /// Addresses to listen on, may be passed more than once. Defaults to an os assigned port on
+    /// every configured transport
     #[arg(short = 'n')]
-    net_addr: Option<String>,
+    net_addrs: Vec<String>,
     #[arg(short = 'a')]
     rpc_addr: Option<String>,
+    /// Address of the boot node, ignored when running as the boot node. Only dialed when no peer
+    /// in the address book can be reached
+    #[arg(short = 'd')]
+    dial_addr: Option<String>,
+    /// Discover peers on the local network, the boot node is then only dialed if `-d` is passed
+    #[arg(long)]
+    mdns: bool,
+    /// Transports to listen & dial on, may be passed more than once. Defaults to quic
+    #[arg(short = 't', long = "transport", value_enum)]
+    transports: Vec<TransportKind>,
+    #[command(flatten)]
+    connection: ConnectionArgs,
 }
 
 #[derive(Subcommand, Debug)]
 enum Command {
-    Provider,
+    Provider {
+        /// Models served by this provider, may be passed more than once
+        #[arg(short = 'm', long = "model")]
+        models: Vec<String>,
+        /// Shell command completing every prompt, it is written the prompt on stdin & its stdout
+        /// is streamed back. Prompts are completed with themselves when unset
+        #[arg(long)]
+        backend_command: Option<String>,
+        /// Most sessions served at once
+        #[arg(long)]
+        max_sessions: Option<usize>,
+        /// Most sessions waiting for a free slot
+        #[arg(long)]
+        queue_size: Option<usize>,
+        /// Seconds a session may take before it is abandoned
+        #[arg(long)]
+        session_timeout: Option<u64>,
+        /// Most input & output tokens a job may have
+        #[arg(long)]
+        max_context: Option<u64>,
+        /// Json file with the pricing policy bids are made with
+        #[arg(long)]
+        pricing: Option<String>,
+    },
     Miner,
 }
 
@@ -55,53 +106,103 @@ async fn main() -> core::MainResult<()> {
         _ => Keypair::generate_ed25519(),
     };
 
+    let mut config = NodeConfig {
+        mdns: args.mdns,
+        ..Default::default()
+    };
+    if !args.transports.is_empty() {
+        config.transports = args.transports;
+    }
+    args.connection.apply(&mut config);
+
+    let listen_addrs: Vec<Multiaddr> = match (args.boot, args.net_addrs.is_empty()) {
+        (true, _) => config
+            .transports
+            .iter()
+            .map(TransportKind::boot_node_listen_addr)
+            .collect(),
+        (false, true) => config
+            .transports
+            .iter()
+            .map(TransportKind::default_listen_addr)
+            .collect(),
+        (false, false) => args
+            .net_addrs
+            .iter()
+            .map(|addr| addr.parse().unwrap())
+            .collect(),
+    };
+
+    let boot_node_addr = match args.boot || (args.mdns && args.dial_addr.is_none()) {
+        true => None,
+        false => Some(match args.dial_addr {
+            Some(addr) => addr.parse::<Multiaddr>().unwrap(),
+            None => config.transports[0].boot_node_local_addr(),
+        }),
+    };
+
     match args.command {
         Command::Miner => {
             let mut node = Node::<MinerNode>::try_from_keys(
                 keypair.clone(),
                 args.rpc_addr.unwrap_or("127.0.0.1:0".to_string()),
+                config,
+                (),
             )
             .await
             .unwrap();
 
-            if args.boot {
-                node.swarm
-                    .listen_on(BOOT_NODE_LISTEN_ADDR.parse().unwrap())
-                    .unwrap();
-            } else {
-                node.swarm
-                    .listen_on(
-                        args.net_addr
-                            .unwrap_or("0.0.0.0:0".to_string())
-                            .parse()
-                            .unwrap(),
-                    )
-                    .unwrap();
+            for addr in listen_addrs {
+                node.listen_on(addr).unwrap();
             }
+            node.bootstrap(boot_node_addr).unwrap();
             node.main_loop().await
         }
 
-        Command::Provider => {
+        Command::Provider {
+            models,
+            backend_command,
+            max_sessions,
+            queue_size,
+            session_timeout,
+            max_context,
+            pricing,
+        } => {
+            let mut provider_config = ProviderNodeConfig::default();
+            if !models.is_empty() {
+                provider_config.models = models;
+            }
+            if let Some(command) = backend_command {
+                provider_config.backend = Arc::new(SubprocessBackend::shell(command));
+            }
+            if let Some(max) = max_sessions {
+                provider_config.max_sessions = max;
+            }
+            if let Some(size) = queue_size {
+                provider_config.queue_size = size;
+            }
+            if let Some(secs) = session_timeout {
+                provider_config.session_timeout = Duration::from_secs(secs);
+            }
+            if let Some(max) = max_context {
+                provider_config.max_context = max;
+            }
+            if let Some(path) = pricing {
+                provider_config.pricing = PricingPolicy::load(path)?;
+            }
+            provider_config.receipts = Receipts::load(config.data_dir.as_deref())?;
             let mut node = Node::<ProviderNode>::try_from_keys(
                 keypair.clone(),
                 args.rpc_addr.unwrap_or("127.0.0.1:0".to_string()),
+                config,
+                provider_config,
             )
             .await
             .unwrap();
-            if args.boot {
-                node.swarm
-                    .listen_on(BOOT_NODE_LISTEN_ADDR.parse().unwrap())
-                    .unwrap();
-            } else {
-                node.swarm
-                    .listen_on(
-                        args.net_addr
-                            .unwrap_or("0.0.0.0:0".to_string())
-                            .parse()
-                            .unwrap(),
-                    )
-                    .unwrap();
+            for addr in listen_addrs {
+                node.listen_on(addr).unwrap();
             }
+            node.bootstrap(boot_node_addr).unwrap();
             node.main_loop().await
         }
     }
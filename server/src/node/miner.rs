@@ -1,41 +1,90 @@
-use core::blockchain::transaction::transfer::Transfer;
-use core::util::map_vec::*;
+use core::behaviour::{
+    gossip::NetworkTopic,
+    rendezvous::{Registrar, RoleNamespace},
+    status::NodeRole,
+};
+use core::blockchain::{
+    block::Block,
+    chain::boot_node_peer_id,
+    transaction::{escrow::Escrow, transfer::Transfer},
+};
+use core::node::behaviour::NodeBehaviourEvent;
+use core::util::{hash::Hash, map_vec::*};
 use core::{node::*, MainResult};
-use libp2p::Swarm;
+use libp2p::{
+    gossipsub,
+    swarm::{NetworkBehaviour, SwarmEvent},
+    Swarm,
+};
 use rpc::RequestWrapper;
+use std::time::{Duration, Instant};
 
 use crate::behaviour::ServerNodeBehaviour;
 
+/// How often pending transfers & escrows are mined into a block
+const BLOCK_INTERVAL: Duration = Duration::from_secs(10);
+/// Leading zeroes a block's hash needs
+const DIFFICULTY: usize = 2;
+
 /// Does some basic POW and validates blocks
 #[derive(Debug)]
 pub struct MinerNode {
+    /// Transfers gossiped on the pending topic that spend unspent outputs, mined at the next
+    /// block
     mempool: MapVec<String, Transfer>,
+    /// Escrows gossiped on the pending escrow topic that apply on top of our chain, mined
+    /// after the transfers
+    escrows: MapVec<String, Escrow>,
+    mine_at: Instant,
+    registrar: Registrar,
 }
 
 #[derive(Debug)]
-pub enum MinerNodeEvent {}
+pub enum MinerNodeEvent {
+    RefreshRegistrations,
+    /// Transfers or escrows are waiting in the mempool & a block is due
+    MineBlock,
+}
 impl NodeTypeEvent for MinerNodeEvent {}
 
 impl NodeType for MinerNode {
     type Behaviour = ServerNodeBehaviour;
     type Event = MinerNodeEvent;
     type RpcRequest = RequestWrapper;
+    type Config = ();
+    const ROLE: NodeRole = NodeRole::Miner;
 
-    fn init_with_swarm(swarm: &mut Swarm<Self::Behaviour>) -> MainResult<Self>
+    fn init_with_swarm(
+        swarm: &mut Swarm<Self::Behaviour>,
+        _config: Self::Config,
+    ) -> MainResult<Self>
     where
         Self: Sized,
     {
+        swarm
+            .behaviour_mut()
+            .shared
+            .gossip
+            .subscribe(&NetworkTopic::PendingTx.subscribe())?;
+        swarm
+            .behaviour_mut()
+            .shared
+            .gossip
+            .subscribe(&NetworkTopic::PendingEscrow.subscribe())?;
         Ok(Self {
             mempool: MapVec::new(),
+            escrows: MapVec::new(),
+            mine_at: Instant::now() + BLOCK_INTERVAL,
+            registrar: Registrar::new([RoleNamespace::Miners]),
         })
     }
     async fn next_event(&mut self) -> MainResult<Option<Self::Event>> {
-        // tokio::select! {
-        // swarm_event = self.swarm.select_next_some() => {
-        //     Ok(Some(NodeEvent::from(swarm_event)))
-        // }
-        // }
-        //
+        if self.registrar.refresh_due() {
+            return Ok(Some(MinerNodeEvent::RefreshRegistrations));
+        }
+        if (self.mempool.len() > 0 || self.escrows.len() > 0) && Instant::now() >= self.mine_at {
+            return Ok(Some(MinerNodeEvent::MineBlock));
+        }
         Ok(None)
     }
 
@@ -44,6 +93,140 @@ impl NodeType for MinerNode {
         Self: Sized,
     {
         tracing::warn!("server event: {e:#?}");
+        match e {
+            MinerNodeEvent::RefreshRegistrations => {
+                node.inner.registrar.register(
+                    &mut node.swarm.behaviour_mut().rendezvous_client,
+                    boot_node_peer_id(),
+                )?;
+            }
+            MinerNodeEvent::MineBlock => {
+                node.inner.mine_at = Instant::now() + BLOCK_INTERVAL;
+                Self::mine_block(node)?;
+            }
+        }
         Ok(())
     }
+
+    async fn handle_swarm_event(
+        node: &mut Node<Self>,
+        e: SwarmEvent<<Self::Behaviour as NetworkBehaviour>::ToSwarm>,
+    ) -> MainResult<Option<SwarmEvent<<Self::Behaviour as NetworkBehaviour>::ToSwarm>>>
+    where
+        Self: Sized,
+    {
+        if let SwarmEvent::ConnectionEstablished { peer_id, .. } = &e {
+            if *peer_id == boot_node_peer_id() {
+                node.inner
+                    .registrar
+                    .register(&mut node.swarm.behaviour_mut().rendezvous_client, *peer_id)?;
+            }
+        }
+
+        match e {
+            SwarmEvent::Behaviour(NodeBehaviourEvent::RendezvousClient(event)) => {
+                node.inner.registrar.handle_event(&event);
+                Ok(None)
+            }
+            SwarmEvent::Behaviour(NodeBehaviourEvent::Gossip(gossipsub::Event::Message {
+                propagation_source,
+                message:
+                    gossipsub::Message {
+                        topic,
+                        data,
+                        source,
+                        ..
+                    },
+                ..
+            })) if topic == NetworkTopic::PendingTx.publish() => {
+                let sender = source.unwrap_or(propagation_source);
+                let transfer: Transfer = match serde_json::from_slice(&data) {
+                    Ok(transfer) => transfer,
+                    Err(err) => {
+                        node.ban_peer(sender, format!("sent an undecodable transfer: {err}"));
+                        return Ok(None);
+                    }
+                };
+                // Republished until it is mined, so it may already be in a block
+                match node.ledger().check(&transfer) {
+                    Ok(()) => node.inner.mempool.push(transfer),
+                    Err(err) => tracing::debug!("dropping transfer {}: {err}", transfer.hash_ref()),
+                }
+                Ok(None)
+            }
+            SwarmEvent::Behaviour(NodeBehaviourEvent::Gossip(gossipsub::Event::Message {
+                propagation_source,
+                message:
+                    gossipsub::Message {
+                        topic,
+                        data,
+                        source,
+                        ..
+                    },
+                ..
+            })) if topic == NetworkTopic::PendingEscrow.publish() => {
+                let sender = source.unwrap_or(propagation_source);
+                let escrow: Escrow = match serde_json::from_slice(&data) {
+                    Ok(escrow) => escrow,
+                    Err(err) => {
+                        node.ban_peer(sender, format!("sent an undecodable escrow: {err}"));
+                        return Ok(None);
+                    }
+                };
+                let height = node.blockchain().len() as u64;
+                match node.ledger().clone().apply_escrow(height, &escrow) {
+                    Ok(()) => node.inner.escrows.push(escrow),
+                    Err(err) => tracing::debug!("dropping escrow {}: {err}", escrow.hash_ref()),
+                }
+                Ok(None)
+            }
+            event => Ok(Some(event)),
+        }
+    }
+}
+
+impl MinerNode {
+    /// Mines every transfer in the mempool that still spends unspent outputs, then every
+    /// escrow that still applies, into a block on top of our chain, the others are dropped
+    fn mine_block(node: &mut Node<Self>) -> MainResult<()> {
+        let mut ledger = node.ledger().clone();
+        let transfers: Vec<Transfer> = std::mem::replace(&mut node.inner.mempool, MapVec::new())
+            .iter_vals()
+            .filter(|transfer| match ledger.spend(transfer) {
+                Ok(()) => true,
+                Err(err) => {
+                    tracing::debug!("dropping transfer {}: {err}", transfer.hash_ref());
+                    false
+                }
+            })
+            .cloned()
+            .collect();
+        let tip = node.blockchain().peek().ok_or("chain is empty")?;
+        let (height, previous) = (tip.index() + 1, tip.hash_ref().to_string());
+        let escrows: Vec<Escrow> = std::mem::replace(&mut node.inner.escrows, MapVec::new())
+            .iter_vals()
+            .filter(|escrow| match ledger.apply_escrow(height, escrow) {
+                Ok(()) => true,
+                Err(err) => {
+                    tracing::debug!("dropping escrow {}: {err}", escrow.hash_ref());
+                    false
+                }
+            })
+            .cloned()
+            .collect();
+        if transfers.is_empty() && escrows.is_empty() {
+            return Ok(());
+        }
+        let count = (transfers.len(), escrows.len());
+        let mut block = Block::new_unsigned(height, 0, previous, transfers, node.keys().public())
+            .with_escrows(escrows);
+        block.mine(DIFFICULTY);
+        let block = block.sign(node.keys())?;
+        tracing::info!(
+            "mined block {height} with {} transfers & {} escrows",
+            count.0,
+            count.1
+        );
+        node.add_block(block)
+    }
 }
@@ -1,80 +1,306 @@
 use behaviour::NodeBehaviourEvent;
 use core::{
     behaviour::{
+        auction::{AuctionMode, AuctionRequest, AuctionTerms, BidMessage, BidReveal, SignedBid},
+        capability::{Capabilities, CapabilityAdvert, ADVERT_INTERVAL, ADVERT_TTL},
+        dht,
         gossip::NetworkTopic,
-        req_res::NetworkResponse,
-        streaming::{echo, STREAM_PROTOCOL},
-        ProvisionBid,
+        pricing::PricingPolicy,
+        receipt::Receipts,
+        rendezvous::{Registrar, RoleNamespace},
+        req_res::{NetworkRequest, NetworkResponse},
+        status::NodeRole,
+        streaming::{serve_session, MessageStream, INFERENCE_PROTOCOL},
+        AuctionId, ProvisionBid,
+    },
+    blockchain::chain::boot_node_peer_id,
+    inference::{
+        jobs::Jobs,
+        mock::MockBackend,
+        throughput::{MeteredBackend, Throughput},
+        InferenceBackend,
     },
     node::*,
-    MainResult,
+    util::now_millis,
+    MainResult, MODEL_ID_0,
+};
+use libp2p::{
+    futures::StreamExt, gossipsub, kad, request_response, swarm::SwarmEvent, PeerId, Swarm,
 };
-use libp2p::{futures::StreamExt, gossipsub, request_response, swarm::SwarmEvent, PeerId, Swarm};
 use rpc::RequestWrapper;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 use tokio::task::JoinHandle;
 
 use crate::behaviour::ServerNodeBehaviour;
 
+/// How long after bidding closes the client may still open a session with us, it may try
+/// other bids first
+const BID_VALIDITY: Duration = Duration::from_secs(60);
+
+/// Wait before advertising again when there was nobody to advertise to
+const ADVERT_RETRY: Duration = Duration::from_secs(5);
+
+/// Auctions we bid in, with the client that started each, when its bidding closed & the
+/// price we bid.
+/// Shared with the listener, sessions are only served for these
+#[derive(Debug, Clone, Default)]
+struct OpenBids(Arc<Mutex<HashMap<AuctionId, (PeerId, u64, f64)>>>);
+
+impl OpenBids {
+    fn insert(&self, auction: AuctionId, client: PeerId, deadline: u64, price: f64) {
+        let mut bids = self.0.lock().unwrap();
+        let now = now_millis();
+        bids.retain(|_, (_, deadline, _)| *deadline + BID_VALIDITY.as_millis() as u64 >= now);
+        bids.insert(auction, (client, deadline, price));
+    }
+
+    /// The price we bid if `client` may open a session in `auction`, only one can be opened
+    /// per bid & none once the bid has expired
+    fn take(&self, auction: AuctionId, client: &PeerId) -> Option<f64> {
+        let mut bids = self.0.lock().unwrap();
+        match bids.get(&auction) {
+            Some((bidder, deadline, _))
+                if bidder == client
+                    && *deadline + BID_VALIDITY.as_millis() as u64 >= now_millis() =>
+            {
+                bids.remove(&auction).map(|(_, _, price)| price)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct SealedBid {
+    client: PeerId,
+    /// When the auction's bidding closes
+    deadline: u64,
+    reveal: BidReveal,
+}
+
 /// provides model work
 
 #[derive(Debug)]
 pub struct ProviderNode {
-    state: ProviderNodeState,
+    /// Accepts inference streams, started once the first client asks to open one
+    listener: Option<JoinHandle<()>>,
+    jobs: Jobs,
+    open_bids: OpenBids,
+    /// Bids committed to in sealed auctions, revealed to their client once bidding closes
+    sealed_bids: Vec<SealedBid>,
+    session_timeout: Duration,
+    /// Measured over the sessions `backend` has served
+    throughput: Throughput,
+    max_context: u64,
+    pricing: PricingPolicy,
+    /// When our capabilities are next advertised
+    advertise_at: Instant,
+    models: Vec<String>,
+    backend: Arc<dyn InferenceBackend>,
+    /// Shared with the listener, every served session's receipt is kept
+    receipts: Arc<Mutex<Receipts>>,
+    registrar: Registrar,
+    /// Whether provider records for `models` have been published to the DHT
+    providing: bool,
 }
-type State = ProviderNodeState;
+
 #[derive(Debug)]
-enum ProviderNodeState {
-    Idle,
-    ListeningForStream(JoinHandle<()>),
+pub struct ProviderNodeConfig {
+    /// Models this provider serves
+    pub models: Vec<String>,
+    /// Completes the prompts of every session
+    pub backend: Arc<dyn InferenceBackend>,
+    /// Most sessions served at once
+    pub max_sessions: usize,
+    /// Most sessions waiting for one of the `max_sessions` slots, clients are turned away and
+    /// no bids are sent once it is full
+    pub queue_size: usize,
+    /// Sessions taking longer are abandoned, freeing their slot
+    pub session_timeout: Duration,
+    /// Most input & output tokens a job may have, auctions for larger ones aren't bid on
+    pub max_context: u64,
+    pub pricing: PricingPolicy,
+    /// Receipts of sessions served before, usually loaded from the data directory
+    pub receipts: Receipts,
+}
+
+impl Default for ProviderNodeConfig {
+    fn default() -> Self {
+        Self {
+            models: vec![MODEL_ID_0.to_string()],
+            backend: Arc::new(MockBackend::default()),
+            max_sessions: 4,
+            queue_size: 8,
+            session_timeout: Duration::from_secs(60 * 5),
+            max_context: 4096,
+            pricing: PricingPolicy::default(),
+            receipts: Receipts::default(),
+        }
+    }
 }
 
 #[derive(Debug)]
-pub enum ProviderNodeEvent {}
+pub enum ProviderNodeEvent {
+    RefreshRegistrations,
+    /// Our capabilities are due to be advertised to clients
+    Advertise,
+    /// Bidding has closed in sealed auctions we committed to bids in
+    RevealBids,
+}
 impl NodeTypeEvent for ProviderNodeEvent {}
 
 impl ProviderNode {
-    fn send_bid(node: &mut Node<Self>, client_peer_id: &PeerId) -> MainResult<()> {
-        let bid = ProvisionBid::new(*node.swarm.local_peer_id(), 5.0);
-        let bytes = serde_json::to_vec(&bid)?;
+    /// The price to bid on the job `terms` describe, if we can take it on for what they offer
+    fn quote(&self, terms: &AuctionTerms) -> Result<f64, String> {
+        if !self.models.contains(&terms.model) {
+            return Err(format!("{} is not served here", terms.model));
+        }
+        if terms.total_tokens() > self.max_context {
+            return Err(format!(
+                "{} tokens don't fit our context",
+                terms.total_tokens()
+            ));
+        }
+        if terms.expired(now_millis()) {
+            return Err("bidding has closed".into());
+        }
+        let load = self.jobs.load();
+        if !load.has_room() {
+            return Err(format!("{} sessions are already queued", load.queued));
+        }
+        let price = self.pricing.price(terms, &load);
+        if price > terms.max_price_per_token {
+            return Err(format!(
+                "{} per token is below our price of {price}",
+                terms.max_price_per_token
+            ));
+        }
+        Ok(price)
+    }
+
+    fn send_bid(
+        node: &mut Node<Self>,
+        client_peer_id: &PeerId,
+        terms: &AuctionTerms,
+        price: f64,
+    ) -> MainResult<()> {
+        let load = node.inner.jobs.load();
+        let mut bid = ProvisionBid::new(*node.swarm.local_peer_id(), price)
+            .with_load(load.utilization())
+            .for_auction(terms.auction)
+            .expiring(terms.closes() + BID_VALIDITY.as_millis() as u64);
+        if let Some(throughput) = node.inner.throughput.tokens_per_sec() {
+            bid = bid.with_throughput(throughput);
+        }
+        let bid = SignedBid::new(bid, node.keys())?;
+        match terms.mode {
+            AuctionMode::Open => Self::publish_bid(node, client_peer_id, &BidMessage::Open(bid)),
+            AuctionMode::Sealed { .. } => {
+                let (commitment, reveal) = BidReveal::seal(bid)?;
+                Self::publish_bid(node, client_peer_id, &BidMessage::Commit(commitment))?;
+                node.inner.sealed_bids.push(SealedBid {
+                    client: *client_peer_id,
+                    deadline: terms.deadline,
+                    reveal,
+                });
+                Ok(())
+            }
+        }
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        let load = self.jobs.load();
+        let room = (load.capacity + load.queue_size).saturating_sub(load.active + load.queued);
+        Capabilities {
+            models: self.models.clone(),
+            max_context: self.max_context,
+            throughput: self.throughput.tokens_per_sec(),
+            capacity: room,
+            price_range: self.pricing.range(&self.models, load.queue_size),
+            expires: now_millis() + ADVERT_TTL.as_millis() as u64,
+        }
+    }
+
+    fn advertise(node: &mut Node<Self>) -> MainResult<()> {
+        let advert = CapabilityAdvert::new(node.inner.capabilities(), node.keys())?;
+        node.swarm.behaviour_mut().shared.gossip.publish(
+            NetworkTopic::Capabilities.publish(),
+            serde_json::to_vec(&advert)?,
+        )?;
+        Ok(())
+    }
+
+    fn publish_bid(
+        node: &mut Node<Self>,
+        client_peer_id: &PeerId,
+        message: &BidMessage,
+    ) -> MainResult<()> {
         node.swarm.behaviour_mut().shared.gossip.publish(
             NetworkTopic::from(client_peer_id).publish(),
-            // not yet sure how to determine bid amounts
-            bytes,
+            serde_json::to_vec(message)?,
         )?;
         Ok(())
     }
 
     fn start_listening_for_stream(node: &mut Node<Self>) -> MainResult<()> {
+        if node.inner.listener.is_some() {
+            return Ok(());
+        }
         let mut incoming_streams = node
             .swarm
             .behaviour_mut()
             .shared
             .stream
             .new_control()
-            .accept(STREAM_PROTOCOL)
+            .accept(INFERENCE_PROTOCOL)
             .unwrap();
+        let models = Arc::new(node.inner.models.clone());
+        let backend = node.inner.backend.clone();
+        let keys = node.keys().clone();
+        let jobs = node.inner.jobs.clone();
+        let open_bids = node.inner.open_bids.clone();
+        let receipts = node.inner.receipts.clone();
+        let session_timeout = node.inner.session_timeout;
 
         let handle = tokio::spawn(async move {
-            // This loop handles incoming streams _sequentially_ but that doesn't have to be the case.
-            // You can also spawn a dedicated task per stream if you want to.
-            // Be aware that this breaks backpressure though as spawning new tasks is equivalent to an
-            // unbounded buffer. Each task needs memory meaning an aggressive remote peer may
-            // force you OOM this way.
-
+            // Every stream gets its own task, the job queue bounds how many there can be
             while let Some((peer, stream)) = incoming_streams.next().await {
-                match echo(stream).await {
-                    Ok(_) => {
-                        tracing::info!(%peer, "Echoed bytes!");
-                    }
-                    Err(e) => {
-                        tracing::error!(%peer, "Echo failed: {e}");
-                        continue;
-                    }
+                let Some(job) = jobs.enqueue() else {
+                    tracing::warn!(%peer, "turning session away, the queue is full");
+                    continue;
                 };
+                let (models, backend, keys) = (models.clone(), backend.clone(), keys.clone());
+                let (open_bids, receipts) = (open_bids.clone(), receipts.clone());
+                tokio::spawn(async move {
+                    let _job = job.start().await;
+                    let stream = MessageStream::new(stream);
+                    let accept = |auction| open_bids.take(auction, &peer);
+                    let session =
+                        serve_session(stream, &keys, &peer, accept, &models, backend.as_ref());
+                    match tokio::time::timeout(session_timeout, session).await {
+                        Ok(Ok(Some(receipt))) => {
+                            tracing::info!(
+                                %peer,
+                                countersigned = receipt.countersigned(),
+                                "session complete, {:?}",
+                                receipt.receipt.usage
+                            );
+                            if let Err(e) = receipts.lock().unwrap().insert(receipt) {
+                                tracing::error!(%peer, "failed to keep receipt: {e}");
+                            }
+                        }
+                        Ok(Ok(None)) => tracing::info!(%peer, "session left before a prompt"),
+                        Ok(Err(e)) => tracing::error!(%peer, "session failed: {e}"),
+                        Err(_) => tracing::warn!(%peer, "session timed out"),
+                    }
+                });
             }
         });
-        let state = State::ListeningForStream(handle);
-        node.inner.state = state;
+        node.inner.listener = Some(handle);
         Ok(())
     }
 }
@@ -83,8 +309,10 @@ impl NodeType for ProviderNode {
     type Behaviour = ServerNodeBehaviour;
     type Event = ProviderNodeEvent;
     type RpcRequest = RequestWrapper;
+    type Config = ProviderNodeConfig;
+    const ROLE: NodeRole = NodeRole::Provider;
 
-    fn init_with_swarm(swarm: &mut Swarm<Self::Behaviour>) -> MainResult<Self>
+    fn init_with_swarm(swarm: &mut Swarm<Self::Behaviour>, config: Self::Config) -> MainResult<Self>
     where
         Self: Sized,
     {
@@ -95,16 +323,43 @@ impl NodeType for ProviderNode {
             .subscribe(&NetworkTopic::Auction.subscribe())
             .expect("failed to sub to auction topic");
 
-        Ok(Self { state: State::Idle })
+        let throughput = Throughput::default();
+        let registrar = Registrar::new(
+            config
+                .models
+                .iter()
+                .map(|model| RoleNamespace::Providers(model)),
+        );
+
+        Ok(Self {
+            listener: None,
+            jobs: Jobs::new(config.max_sessions, config.queue_size),
+            open_bids: OpenBids::default(),
+            sealed_bids: vec![],
+            session_timeout: config.session_timeout,
+            backend: Arc::new(MeteredBackend::new(config.backend, throughput.clone())),
+            throughput,
+            max_context: config.max_context,
+            pricing: config.pricing,
+            advertise_at: Instant::now(),
+            models: config.models,
+            receipts: Arc::new(Mutex::new(config.receipts)),
+            registrar,
+            providing: false,
+        })
     }
 
     async fn next_event(&mut self) -> MainResult<Option<Self::Event>> {
-        // tokio::select! {
-        // swarm_event = self.swarm.select_next_some() => {
-        //     Ok(Some(NodeEvent::from(swarm_event)))
-        // }
-        // }
-        //
+        if self.registrar.refresh_due() {
+            return Ok(Some(ProviderNodeEvent::RefreshRegistrations));
+        }
+        if Instant::now() >= self.advertise_at {
+            return Ok(Some(ProviderNodeEvent::Advertise));
+        }
+        let now = now_millis();
+        if self.sealed_bids.iter().any(|sealed| now > sealed.deadline) {
+            return Ok(Some(ProviderNodeEvent::RevealBids));
+        }
         Ok(None)
     }
     async fn handle_self_event(node: &mut Node<Self>, e: Self::Event) -> MainResult<()>
@@ -112,6 +367,38 @@ impl NodeType for ProviderNode {
         Self: Sized,
     {
         tracing::warn!("server event: {e:#?}");
+        match e {
+            ProviderNodeEvent::RefreshRegistrations => {
+                tracing::info!("refreshing registrations for {:?}", node.inner.models);
+                node.inner.registrar.register(
+                    &mut node.swarm.behaviour_mut().rendezvous_client,
+                    boot_node_peer_id(),
+                )?;
+            }
+            ProviderNodeEvent::Advertise => {
+                node.inner.advertise_at = match Self::advertise(node) {
+                    Ok(()) => Instant::now() + ADVERT_INTERVAL,
+                    Err(err) => {
+                        tracing::debug!("failed to advertise our capabilities: {err}");
+                        Instant::now() + ADVERT_RETRY
+                    }
+                };
+            }
+            ProviderNodeEvent::RevealBids => {
+                let now = now_millis();
+                let (due, waiting): (Vec<_>, Vec<_>) = std::mem::take(&mut node.inner.sealed_bids)
+                    .into_iter()
+                    .partition(|sealed| now > sealed.deadline);
+                node.inner.sealed_bids = waiting;
+                for SealedBid { client, reveal, .. } in due {
+                    let auction = reveal.bid.bid.auction;
+                    if let Err(err) = Self::publish_bid(node, &client, &BidMessage::Reveal(reveal))
+                    {
+                        tracing::warn!("failed to reveal our bid in auction {auction:?}: {err}");
+                    }
+                }
+            }
+        }
         Ok(())
     }
 
@@ -130,23 +417,44 @@ impl NodeType for ProviderNode {
     where
         Self: Sized,
     {
-        match (_e, &node.inner.state) {
-            (
-                SwarmEvent::Behaviour(NodeBehaviourEvent::ReqRes(
-                    libp2p::request_response::Event::Message {
-                        peer,
-                        message:
-                            request_response::Message::Request {
-                                request_id,
-                                request,
-                                channel,
-                            },
-                    },
-                )),
-                State::Idle,
-            ) => {
-                // Provider is already listening, it cannot connect
-                if let State::ListeningForStream(_) = node.inner.state {
+        if let SwarmEvent::ConnectionEstablished { peer_id, .. } = &_e {
+            if *peer_id == boot_node_peer_id() {
+                node.inner
+                    .registrar
+                    .register(&mut node.swarm.behaviour_mut().rendezvous_client, *peer_id)?;
+            }
+        }
+
+        match _e {
+            SwarmEvent::Behaviour(NodeBehaviourEvent::RendezvousClient(event)) => {
+                node.inner.registrar.handle_event(&event);
+                Ok(None)
+            }
+            // Provider records can only be stored once there are peers in the routing table
+            SwarmEvent::Behaviour(NodeBehaviourEvent::Kad(kad::Event::RoutingUpdated {
+                ..
+            })) if !node.inner.providing => {
+                dht::provide(
+                    &mut node.swarm.behaviour_mut().shared.kad,
+                    node.inner.models.iter(),
+                )?;
+                node.inner.providing = true;
+                Ok(None)
+            }
+            SwarmEvent::Behaviour(NodeBehaviourEvent::ReqRes(
+                libp2p::request_response::Event::Message {
+                    peer,
+                    message:
+                        request_response::Message::Request {
+                            request: NetworkRequest::OpenStream,
+                            channel,
+                            ..
+                        },
+                },
+            )) => {
+                // Every slot is taken & the queue is full, the client should try someone else
+                if !node.inner.jobs.load().has_room() {
+                    tracing::warn!(%peer, "turning stream away, the queue is full");
                     node.swarm
                         .behaviour_mut()
                         .shared
@@ -165,30 +473,58 @@ impl NodeType for ProviderNode {
                     .unwrap();
                 Ok(None)
             }
-            (
-                SwarmEvent::Behaviour(NodeBehaviourEvent::Gossip(
-                    libp2p::gossipsub::Event::Message {
-                        message:
-                            gossipsub::Message {
-                                topic,
-                                data,
-                                source,
-                                ..
-                            },
-                        ..
-                    },
-                )),
-                State::Idle,
-            ) if topic == NetworkTopic::Auction.publish() => {
-                ProviderNode::send_bid(
-                    node,
-                    source
-                        .as_ref()
-                        .expect("no source from auction start gossip"),
-                )?;
+            SwarmEvent::Behaviour(NodeBehaviourEvent::Gossip(
+                libp2p::gossipsub::Event::Message {
+                    message:
+                        gossipsub::Message {
+                            topic,
+                            data,
+                            source,
+                            ..
+                        },
+                    ..
+                },
+            )) if topic == NetworkTopic::Auction.publish() => {
+                let Some(client) = source else {
+                    return Ok(None);
+                };
+                let request: AuctionRequest = match serde_json::from_slice(&data) {
+                    Ok(request) => request,
+                    Err(err) => {
+                        node.ban_peer(client, format!("sent an undecodable auction: {err}"));
+                        return Ok(None);
+                    }
+                };
+                match request.verify() {
+                    Ok(signer) if signer == client => {}
+                    Ok(signer) => {
+                        node.ban_peer(client, format!("relayed an auction signed by {signer}"));
+                        return Ok(None);
+                    }
+                    Err(err) => {
+                        node.ban_peer(client, err.to_string());
+                        return Ok(None);
+                    }
+                }
+                let auction = request.terms.auction;
+                let price = match node.inner.quote(&request.terms) {
+                    Ok(price) => price,
+                    Err(reason) => {
+                        tracing::debug!("not bidding in auction {auction}: {reason}");
+                        return Ok(None);
+                    }
+                };
+                match ProviderNode::send_bid(node, &client, &request.terms, price) {
+                    Ok(()) => {
+                        node.inner
+                            .open_bids
+                            .insert(auction, client, request.terms.closes(), price);
+                    }
+                    Err(err) => tracing::warn!("failed to bid in auction {auction}: {err}"),
+                }
                 Ok(None)
             }
-            (event, _state) => return Ok(Some(event)),
+            event => return Ok(Some(event)),
         }
     }
 }
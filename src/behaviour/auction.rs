@@ -0,0 +1,182 @@
+use libp2p::{
+    identity::{Keypair, PublicKey},
+    PeerId,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{AuctionId, ProvisionBid};
+use crate::{util::PublicKeyBytes, MainResult};
+
+/// What a client is asking providers to bid on, everything here is signed
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct AuctionTerms {
+    pub auction: AuctionId,
+    pub model: String,
+    pub input_tokens: u64,
+    pub expected_output_tokens: u64,
+    /// Most the client will pay for each input or output token
+    pub max_price_per_token: f64,
+    /// Milliseconds since the unix epoch after which bids are ignored
+    pub deadline: u64,
+    #[serde(default)]
+    pub mode: AuctionMode,
+}
+
+/// How providers bid in an auction
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum AuctionMode {
+    /// Bids are published as they are made
+    #[default]
+    Open,
+    /// Providers publish a commitment to their bid before the deadline & reveal the bid after
+    /// it, so competitors can't undercut bids they've seen
+    Sealed {
+        /// Milliseconds since the unix epoch after which reveals are ignored
+        reveal_deadline: u64,
+    },
+}
+
+/// Gossiped on `NetworkTopic::Auction` to ask providers for bids
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct AuctionRequest {
+    pub terms: AuctionTerms,
+    client: PublicKeyBytes,
+    signature: Vec<u8>,
+}
+
+/// Gossiped by a provider to the client it is bidding for, signed so it can't be impersonated
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct SignedBid {
+    pub bid: ProvisionBid,
+    provider: PublicKeyBytes,
+    signature: Vec<u8>,
+}
+
+/// Published by providers on the topic of the client running the auction
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BidMessage {
+    /// A bid in an open auction
+    Open(SignedBid),
+    Commit(BidCommitment),
+    Reveal(BidReveal),
+}
+
+/// Binds a provider to a sealed bid without showing it, the gossip signature ties it to `peer`
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct BidCommitment {
+    pub auction: AuctionId,
+    pub peer: PeerId,
+    hash: [u8; 32],
+}
+
+/// Opens a `BidCommitment` once bidding has closed
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct BidReveal {
+    pub bid: SignedBid,
+    nonce: [u8; 32],
+}
+
+impl AuctionTerms {
+    /// Tokens the client expects to pay for
+    pub fn total_tokens(&self) -> u64 {
+        self.input_tokens + self.expected_output_tokens
+    }
+
+    pub fn expired(&self, now_millis: u64) -> bool {
+        now_millis > self.deadline
+    }
+
+    /// When the client stops taking bids, or reveals of sealed ones
+    pub fn closes(&self) -> u64 {
+        match self.mode {
+            AuctionMode::Open => self.deadline,
+            AuctionMode::Sealed { reveal_deadline } => reveal_deadline,
+        }
+    }
+
+    pub fn closed(&self, now_millis: u64) -> bool {
+        now_millis > self.closes()
+    }
+}
+
+impl AuctionRequest {
+    pub fn new(terms: AuctionTerms, keys: &Keypair) -> MainResult<Self> {
+        let signature = keys.sign(&serde_json::to_vec(&terms)?)?;
+        Ok(Self {
+            terms,
+            client: keys.public().into(),
+            signature,
+        })
+    }
+
+    /// Returns the client that signed the request, erroring if the signature is invalid
+    pub fn verify(&self) -> MainResult<PeerId> {
+        let client: PublicKey = (&self.client).try_into()?;
+        if !client.verify(&serde_json::to_vec(&self.terms)?, &self.signature) {
+            return Err("auction request has an invalid signature".into());
+        }
+        Ok(client.to_peer_id())
+    }
+}
+
+impl SignedBid {
+    pub fn new(bid: ProvisionBid, keys: &Keypair) -> MainResult<Self> {
+        let signature = keys.sign(&serde_json::to_vec(&bid)?)?;
+        Ok(Self {
+            bid,
+            provider: keys.public().into(),
+            signature,
+        })
+    }
+
+    /// Returns the provider that signed the bid, erroring if the signature is invalid or the
+    /// bid claims to be from another peer
+    pub fn verify(&self) -> MainResult<PeerId> {
+        let provider: PublicKey = (&self.provider).try_into()?;
+        if !provider.verify(&serde_json::to_vec(&self.bid)?, &self.signature) {
+            return Err("bid has an invalid signature".into());
+        }
+        let signer = provider.to_peer_id();
+        if signer != self.bid.peer {
+            return Err(format!("bid from {} is signed by {signer}", self.bid.peer).into());
+        }
+        Ok(signer)
+    }
+}
+
+impl BidCommitment {
+    fn hash(bid: &ProvisionBid, nonce: &[u8; 32]) -> MainResult<[u8; 32]> {
+        let mut hasher = Sha256::new();
+        hasher.update(serde_json::to_vec(bid)?);
+        hasher.update(nonce);
+        Ok(hasher.finalize().into())
+    }
+
+    /// Whether `reveal` is the bid this commits to
+    pub fn opened_by(&self, reveal: &BidReveal) -> bool {
+        let bid = &reveal.bid.bid;
+        bid.auction == Some(self.auction)
+            && bid.peer == self.peer
+            && Self::hash(bid, &reveal.nonce).is_ok_and(|hash| hash == self.hash)
+    }
+}
+
+impl BidReveal {
+    /// Commits to `bid` under a random nonce, the reveal is kept until bidding closes
+    pub fn seal(bid: SignedBid) -> MainResult<(BidCommitment, Self)> {
+        let auction = bid
+            .bid
+            .auction
+            .ok_or("only bids in an auction can be sealed")?;
+        let nonce: [u8; 32] = rand::random();
+        let commitment = BidCommitment {
+            auction,
+            peer: bid.bid.peer,
+            hash: BidCommitment::hash(&bid.bid, &nonce)?,
+        };
+        Ok((commitment, Self { bid, nonce }))
+    }
+}
@@ -0,0 +1,155 @@
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt::Debug, sync::Arc, time::Duration};
+
+use super::{auction::AuctionTerms, ProvisionBid};
+
+/// A bid along with what the client observed about the provider that made it
+#[derive(Debug, Clone, PartialEq)]
+pub struct CandidateBid {
+    pub bid: ProvisionBid,
+    /// Time between the auction being published & the bid arriving
+    pub latency: Duration,
+    /// Share of our sessions with the provider that completed, see `Reputation`
+    pub reputation: f64,
+}
+
+/// Ranks the bids of an auction for a client
+pub trait BidStrategy: Debug + Send + Sync {
+    /// Higher scores are better, None if the bid shouldn't be accepted at all
+    fn score(&self, candidate: &CandidateBid, terms: &AuctionTerms) -> Option<f64>;
+
+    /// The best acceptable candidate, earlier bids win ties
+    fn choose<'c>(
+        &self,
+        candidates: &'c [CandidateBid],
+        terms: &AuctionTerms,
+    ) -> Option<&'c CandidateBid> {
+        let mut best: Option<(&CandidateBid, f64)> = None;
+        for candidate in candidates {
+            let Some(score) = self.score(candidate, terms) else {
+                continue;
+            };
+            if best.is_none_or(|(_, best)| score > best) {
+                best = Some((candidate, score));
+            }
+        }
+        best.map(|(candidate, _)| candidate)
+    }
+}
+
+fn within_budget(candidate: &CandidateBid, terms: &AuctionTerms) -> bool {
+    candidate.bid.bid <= terms.max_price_per_token
+}
+
+/// Picks the lowest price among bids that are within budget & meet the optional limits
+#[derive(Debug, Clone, Default)]
+pub struct CheapestAcceptable {
+    pub max_latency: Option<Duration>,
+    pub min_reputation: f64,
+}
+
+impl BidStrategy for CheapestAcceptable {
+    fn score(&self, candidate: &CandidateBid, terms: &AuctionTerms) -> Option<f64> {
+        let acceptable = within_budget(candidate, terms)
+            && self.max_latency.is_none_or(|max| candidate.latency <= max)
+            && candidate.reputation >= self.min_reputation;
+        acceptable.then_some(-candidate.bid.bid)
+    }
+}
+
+/// Trades every factor off against the others, bids within budget score
+/// `reputation * reputation_weight + throughput * throughput_weight
+///     - price * price_weight - latency secs * latency_weight`
+#[derive(Debug, Clone)]
+pub struct Weighted {
+    pub price: f64,
+    pub latency: f64,
+    pub throughput: f64,
+    pub reputation: f64,
+}
+
+impl Default for Weighted {
+    fn default() -> Self {
+        Self {
+            price: 1.,
+            latency: 1.,
+            throughput: 0.05,
+            reputation: 5.,
+        }
+    }
+}
+
+impl BidStrategy for Weighted {
+    fn score(&self, candidate: &CandidateBid, terms: &AuctionTerms) -> Option<f64> {
+        if !within_budget(candidate, terms) {
+            return None;
+        }
+        let throughput = candidate.bid.throughput.unwrap_or_default() as f64;
+        Some(
+            candidate.reputation * self.reputation + throughput * self.throughput
+                - candidate.bid.bid * self.price
+                - candidate.latency.as_secs_f64() * self.latency,
+        )
+    }
+}
+
+/// Built in strategies, selectable from the command line & over rpc.
+/// Serialized as a single digit so an rpc request carrying one stays small
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(into = "u8", try_from = "u8")]
+#[repr(u8)]
+pub enum BidStrategyKind {
+    #[default]
+    Cheapest = 0,
+    Weighted = 1,
+}
+
+impl From<BidStrategyKind> for u8 {
+    fn from(kind: BidStrategyKind) -> Self {
+        kind as u8
+    }
+}
+
+impl TryFrom<u8> for BidStrategyKind {
+    type Error = String;
+    fn try_from(byte: u8) -> Result<Self, Self::Error> {
+        match byte {
+            0 => Ok(Self::Cheapest),
+            1 => Ok(Self::Weighted),
+            _ => Err(format!("{byte} is not a bid strategy")),
+        }
+    }
+}
+
+impl BidStrategyKind {
+    pub fn strategy(self) -> Arc<dyn BidStrategy> {
+        match self {
+            Self::Cheapest => Arc::new(CheapestAcceptable::default()),
+            Self::Weighted => Arc::new(Weighted::default()),
+        }
+    }
+}
+
+/// How sessions with each provider have gone
+#[derive(Debug, Default)]
+pub struct Reputation {
+    /// Completed & failed sessions
+    sessions: HashMap<PeerId, (u32, u32)>,
+}
+
+impl Reputation {
+    pub fn record(&mut self, provider: PeerId, completed: bool) {
+        let (completions, failures) = self.sessions.entry(provider).or_default();
+        match completed {
+            true => *completions += 1,
+            false => *failures += 1,
+        }
+    }
+
+    /// Share of sessions that completed, providers we haven't used sit at 0.5
+    pub fn score(&self, provider: &PeerId) -> f64 {
+        let (completions, failures) = self.sessions.get(provider).copied().unwrap_or_default();
+        (completions as f64 + 1.) / ((completions + failures) as f64 + 2.)
+    }
+}
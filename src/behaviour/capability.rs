@@ -0,0 +1,98 @@
+use libp2p::{
+    identity::{Keypair, PublicKey},
+    PeerId,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, time::Duration};
+
+use super::auction::AuctionTerms;
+use crate::{util::PublicKeyBytes, MainResult};
+
+/// How often providers advertise their capabilities
+pub const ADVERT_INTERVAL: Duration = Duration::from_secs(30);
+/// Longest an advert is trusted for, providers that stop advertising drop out of directories
+pub const ADVERT_TTL: Duration = Duration::from_secs(90);
+
+/// What a provider can take on
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Capabilities {
+    pub models: Vec<String>,
+    /// Most input & output tokens a single job may have
+    pub max_context: u64,
+    /// Tokens per second the provider's backend has been measured generating, None until it
+    /// has completed a session
+    pub throughput: Option<f32>,
+    /// Sessions the provider could still take on, counting room in its queue
+    pub capacity: usize,
+    /// Lowest & highest price per token the provider bids
+    pub price_range: (f64, f64),
+    /// Milliseconds since the unix epoch after which the advert is stale
+    pub expires: u64,
+}
+
+/// Gossiped on `NetworkTopic::Capabilities` by every provider
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct CapabilityAdvert {
+    pub capabilities: Capabilities,
+    provider: PublicKeyBytes,
+    signature: Vec<u8>,
+}
+
+impl Capabilities {
+    /// Whether the provider could bid on the job `terms` describe, ignoring how busy it is
+    pub fn suits(&self, terms: &AuctionTerms) -> bool {
+        self.models.contains(&terms.model)
+            && terms.total_tokens() <= self.max_context
+            && self.price_range.0 <= terms.max_price_per_token
+    }
+}
+
+impl CapabilityAdvert {
+    pub fn new(capabilities: Capabilities, keys: &Keypair) -> MainResult<Self> {
+        let signature = keys.sign(&serde_json::to_vec(&capabilities)?)?;
+        Ok(Self {
+            capabilities,
+            provider: keys.public().into(),
+            signature,
+        })
+    }
+
+    /// Returns the provider that signed the advert, erroring if the signature is invalid
+    pub fn verify(&self) -> MainResult<PeerId> {
+        let provider: PublicKey = (&self.provider).try_into()?;
+        if !provider.verify(&serde_json::to_vec(&self.capabilities)?, &self.signature) {
+            return Err("capability advert has an invalid signature".into());
+        }
+        Ok(provider.to_peer_id())
+    }
+}
+
+/// The latest advert of every provider, kept until it expires
+#[derive(Debug, Default)]
+pub struct ProviderDirectory {
+    adverts: HashMap<PeerId, Capabilities>,
+}
+
+impl ProviderDirectory {
+    /// Replaces the provider's last advert, it is trusted for `ADVERT_TTL` at most
+    pub fn insert(&mut self, provider: PeerId, mut capabilities: Capabilities, now_millis: u64) {
+        let longest = now_millis + ADVERT_TTL.as_millis() as u64;
+        capabilities.expires = capabilities.expires.min(longest);
+        self.adverts.insert(provider, capabilities);
+        self.adverts
+            .retain(|_, advert| advert.expires >= now_millis);
+    }
+
+    /// Every provider whose advert hasn't expired
+    pub fn providers(&self, now_millis: u64) -> impl Iterator<Item = (&PeerId, &Capabilities)> {
+        self.adverts
+            .iter()
+            .filter(move |(_, advert)| advert.expires >= now_millis)
+    }
+
+    /// Whether anyone could bid on the job `terms` describe, true while nobody has advertised
+    pub fn anyone_suits(&self, terms: &AuctionTerms, now_millis: u64) -> bool {
+        let mut providers = self.providers(now_millis).peekable();
+        providers.peek().is_none() || providers.any(|(_, advert)| advert.suits(terms))
+    }
+}
@@ -0,0 +1,121 @@
+use libp2p::{
+    kad::{self, store::MemoryStore, GetProvidersOk, QueryId, QueryResult, RecordKey},
+    PeerId, StreamProtocol,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+use crate::MainResult;
+
+pub const KAD_PROTOCOL: StreamProtocol = StreamProtocol::new("/kad/1.0.0");
+pub const KAD_QUERY_TIMEOUT: Duration = Duration::from_secs(10);
+const MODEL_KEY_PREFIX: &str = "/model/";
+/// How long a provider found by a lookup is trusted to serve the model without being found
+/// again
+pub const PROVIDER_RECORD_TTL: Duration = Duration::from_secs(10 * 60);
+
+pub type Kademlia = kad::Behaviour<MemoryStore>;
+
+/// Key that providers of the given model publish provider records under
+pub fn model_key(model: &str) -> RecordKey {
+    RecordKey::new(&format!("{MODEL_KEY_PREFIX}{model}"))
+}
+
+/// Announce to the DHT that we serve each of the given models
+pub fn provide<'m>(
+    kad: &mut Kademlia,
+    models: impl IntoIterator<Item = &'m String>,
+) -> MainResult<()> {
+    for model in models {
+        kad.start_providing(model_key(model))?;
+        tracing::info!("providing {model}");
+    }
+    Ok(())
+}
+
+/// Tracks lookups of which peers provide which models. Providers are forgotten once they
+/// disconnect or haven't been found for `PROVIDER_RECORD_TTL`
+#[derive(Debug, Default)]
+pub struct ProviderRecords {
+    queries: HashMap<QueryId, String>,
+    /// When each provider of a model was last found
+    providers: HashMap<String, HashMap<PeerId, Instant>>,
+}
+
+impl ProviderRecords {
+    /// Starts looking up the providers of a model, results are collected by `handle_event`
+    pub fn lookup(&mut self, kad: &mut Kademlia, model: &str) -> QueryId {
+        let id = kad.get_providers(model_key(model));
+        self.queries.insert(id, model.to_string());
+        id
+    }
+
+    /// Returns the query id & model of a lookup once it has finished
+    pub fn handle_event(&mut self, event: &kad::Event) -> Option<(QueryId, String)> {
+        if let kad::Event::OutboundQueryProgressed {
+            id,
+            result: QueryResult::GetProviders(result),
+            step,
+            ..
+        } = event
+        {
+            let model = self.queries.get(id)?;
+            match result {
+                Ok(GetProvidersOk::FoundProviders { providers, .. }) => {
+                    tracing::info!("found providers for {model}: {providers:?}");
+                    let model = model.to_owned();
+                    self.found(&model, providers.iter().copied(), Instant::now());
+                }
+                Ok(GetProvidersOk::FinishedWithNoAdditionalRecord { .. }) => {}
+                Err(err) => tracing::warn!("provider lookup for {model} failed: {err:?}"),
+            }
+            if step.last {
+                self.expire(Instant::now());
+                return self.queries.remove(id).map(|model| (*id, model));
+            }
+        }
+        None
+    }
+
+    /// Records that `providers` were found serving `model` at `now`
+    pub fn found(
+        &mut self,
+        model: &str,
+        providers: impl IntoIterator<Item = PeerId>,
+        now: Instant,
+    ) {
+        let known = self.providers.entry(model.to_string()).or_default();
+        for provider in providers {
+            known.insert(provider, now);
+        }
+    }
+
+    /// Forgets a provider that went away, for every model
+    pub fn forget(&mut self, peer: &PeerId) {
+        for known in self.providers.values_mut() {
+            known.remove(peer);
+        }
+        self.providers.retain(|_, known| !known.is_empty());
+    }
+
+    /// Forgets providers that haven't been found for `PROVIDER_RECORD_TTL` by `now`
+    pub fn expire(&mut self, now: Instant) {
+        for known in self.providers.values_mut() {
+            known.retain(|_, found| now.duration_since(*found) < PROVIDER_RECORD_TTL);
+        }
+        self.providers.retain(|_, known| !known.is_empty());
+    }
+
+    /// Peers known to provide the given model at `now`, empty if none are
+    pub fn providers(&self, model: &str, now: Instant) -> HashSet<PeerId> {
+        self.providers
+            .get(model)
+            .into_iter()
+            .flatten()
+            .filter(|(_, found)| now.duration_since(**found) < PROVIDER_RECORD_TTL)
+            .map(|(peer, _)| *peer)
+            .collect()
+    }
+}
@@ -0,0 +1,189 @@
+use chacha20poly1305::{aead::Aead, ChaCha20Poly1305, Key, KeyInit, Nonce};
+use hkdf::Hkdf;
+use libp2p::{
+    identity::{Keypair, PublicKey},
+    PeerId,
+};
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use x25519_dalek::{EphemeralSecret, PublicKey as EphemeralPublicKey};
+
+use super::AuctionId;
+use crate::{util::PublicKeyBytes, MainResult};
+
+/// Prefixed to every signed ephemeral key so the signature can't be replayed anywhere else
+const HANDSHAKE_DOMAIN: &[u8] = b"/inference/handshake";
+/// Key used for frames sent by the client, the side opening the session
+const CLIENT_KEY_INFO: &[u8] = b"client to provider";
+/// Key used for frames sent by the provider
+const PROVIDER_KEY_INFO: &[u8] = b"provider to client";
+
+/// Sent in the clear by both sides before anything else.
+/// The ephemeral key is signed by the sender's identity so whatever relays the stream can't
+/// substitute its own, along with the auction the session is for
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Handshake {
+    auction: AuctionId,
+    ephemeral: [u8; 32],
+    identity: PublicKeyBytes,
+    signature: Vec<u8>,
+}
+
+impl Handshake {
+    fn signed_bytes(auction: AuctionId, ephemeral: &[u8; 32]) -> Vec<u8> {
+        [
+            HANDSHAKE_DOMAIN,
+            auction.0.to_be_bytes().as_slice(),
+            ephemeral.as_slice(),
+        ]
+        .concat()
+    }
+
+    fn new(keys: &Keypair, auction: AuctionId, ephemeral: &EphemeralPublicKey) -> MainResult<Self> {
+        let ephemeral = ephemeral.to_bytes();
+        Ok(Self {
+            signature: keys.sign(&Self::signed_bytes(auction, &ephemeral))?,
+            identity: keys.public().into(),
+            auction,
+            ephemeral,
+        })
+    }
+
+    /// The auction the sender wants to open a session for, only trustworthy once the
+    /// handshake is finished
+    pub fn auction(&self) -> AuctionId {
+        self.auction
+    }
+
+    /// Returns the ephemeral key if the handshake was signed by `peer`
+    fn verify(&self, peer: &PeerId) -> MainResult<EphemeralPublicKey> {
+        let identity: PublicKey = (&self.identity).try_into()?;
+        if identity.to_peer_id() != *peer {
+            return Err(format!("handshake was not sent by {peer}").into());
+        }
+        if !identity.verify(
+            &Self::signed_bytes(self.auction, &self.ephemeral),
+            &self.signature,
+        ) {
+            return Err(format!("invalid handshake signature from {peer}").into());
+        }
+        Ok(EphemeralPublicKey::from(self.ephemeral))
+    }
+}
+
+/// Which end of the session we are, each direction is encrypted with its own key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Client,
+    Provider,
+}
+
+/// Half of a key agreement, kept until the other side's handshake arrives
+pub struct PendingHandshake {
+    secret: EphemeralSecret,
+    ours: Handshake,
+    side: Side,
+}
+
+impl PendingHandshake {
+    pub fn new(keys: &Keypair, side: Side, auction: AuctionId) -> MainResult<Self> {
+        let secret = EphemeralSecret::random_from_rng(OsRng);
+        let ours = Handshake::new(keys, auction, &EphemeralPublicKey::from(&secret))?;
+        Ok(Self { secret, ours, side })
+    }
+
+    /// What has to be sent to the other side
+    pub fn handshake(&self) -> &Handshake {
+        &self.ours
+    }
+
+    /// Derives the session keys from `theirs`, which has to have been sent by `peer`
+    pub fn finish(self, theirs: &Handshake, peer: &PeerId) -> MainResult<SessionCipher> {
+        let their_key = theirs.verify(peer)?;
+        if theirs.auction != self.ours.auction {
+            return Err(format!(
+                "handshake is for auction {}, not {}",
+                theirs.auction, self.ours.auction
+            )
+            .into());
+        }
+        let shared = self.secret.diffie_hellman(&their_key);
+        if !shared.was_contributory() {
+            return Err("handshake used a low order key".into());
+        }
+
+        // both ephemeral keys salt the derivation, binding the keys to this exact exchange
+        let (client, provider) = match self.side {
+            Side::Client => (&self.ours, theirs),
+            Side::Provider => (theirs, &self.ours),
+        };
+        let salt = [client.ephemeral, provider.ephemeral].concat();
+        let hkdf = Hkdf::<Sha256>::new(Some(&salt), shared.as_bytes());
+        let derive = |info: &[u8]| -> MainResult<ChaCha20Poly1305> {
+            let mut key = [0u8; 32];
+            hkdf.expand(info, &mut key)
+                .map_err(|_| "failed to derive session key")?;
+            Ok(ChaCha20Poly1305::new(Key::from_slice(&key)))
+        };
+        let (sending, receiving) = match self.side {
+            Side::Client => (derive(CLIENT_KEY_INFO)?, derive(PROVIDER_KEY_INFO)?),
+            Side::Provider => (derive(PROVIDER_KEY_INFO)?, derive(CLIENT_KEY_INFO)?),
+        };
+        Ok(SessionCipher {
+            sending,
+            receiving,
+            sent: 0,
+            received: 0,
+        })
+    }
+}
+
+/// Authenticated encryption for every frame of a session.
+/// Nonces are per direction counters, so frames that are dropped, reordered or replayed fail
+/// to decrypt
+#[derive(Clone)]
+pub struct SessionCipher {
+    sending: ChaCha20Poly1305,
+    receiving: ChaCha20Poly1305,
+    sent: u64,
+    received: u64,
+}
+
+impl std::fmt::Debug for SessionCipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SessionCipher")
+            .field("sent", &self.sent)
+            .field("received", &self.received)
+            .finish_non_exhaustive()
+    }
+}
+
+fn nonce(counter: u64) -> Nonce {
+    let mut nonce = [0u8; 12];
+    nonce[4..].copy_from_slice(&counter.to_be_bytes());
+    *Nonce::from_slice(&nonce)
+}
+
+impl SessionCipher {
+    pub fn encrypt(&mut self, plaintext: &[u8]) -> MainResult<Vec<u8>> {
+        let ciphertext = self
+            .sending
+            .encrypt(&nonce(self.sent), plaintext)
+            .map_err(|_| "failed to encrypt frame")?;
+        self.sent = self.sent.checked_add(1).ok_or("session nonces exhausted")?;
+        Ok(ciphertext)
+    }
+
+    pub fn decrypt(&mut self, ciphertext: &[u8]) -> MainResult<Vec<u8>> {
+        let plaintext = self
+            .receiving
+            .decrypt(&nonce(self.received), ciphertext)
+            .map_err(|_| "frame failed to decrypt")?;
+        self.received = self
+            .received
+            .checked_add(1)
+            .ok_or("session nonces exhausted")?;
+        Ok(plaintext)
+    }
+}
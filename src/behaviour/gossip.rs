@@ -6,12 +6,17 @@ use libp2p::{
 pub enum NetworkTopic<'t> {
     /// All validators subscribe to pending topic, everyone else need only publish
     PendingTx,
+    /// Validators subscribe to escrow locks, claims & refunds waiting to be mined, everyone
+    /// else need only publish
+    PendingEscrow,
     /// All nodes subscribe to this topic, only validators publish
     ChainUpdate,
     /// All providers subscribe to Auction topic, clients need only to publish
     Auction,
     /// Clients each subscribe to their own topic, providers publish when bidding
     Client(&'t PeerId),
+    /// Clients subscribe to this topic, providers publish their capabilities
+    Capabilities,
 }
 
 impl<'t> From<&'t PeerId> for NetworkTopic<'t> {
@@ -23,13 +28,17 @@ impl<'t> From<&'t PeerId> for NetworkTopic<'t> {
 impl<'t> NetworkTopic<'t> {
     const AUCTION: &'t str = "auction";
     const PENDING_TX: &'t str = "pending";
+    const PENDING_ESCROW: &'t str = "pending_escrow";
     const CHAIN_UPDATE: &'t str = "chain_update";
+    const CAPABILITIES: &'t str = "capabilities";
     pub fn publish(&self) -> TopicHash {
         match self {
             Self::Auction => TopicHash::from_raw(Self::AUCTION),
             Self::PendingTx => TopicHash::from_raw(Self::PENDING_TX),
+            Self::PendingEscrow => TopicHash::from_raw(Self::PENDING_ESCROW),
             Self::ChainUpdate => TopicHash::from_raw(Self::CHAIN_UPDATE),
             Self::Client(peer) => TopicHash::from_raw(peer.to_string()),
+            Self::Capabilities => TopicHash::from_raw(Self::CAPABILITIES),
         }
     }
 
@@ -37,8 +46,10 @@ impl<'t> NetworkTopic<'t> {
         match self {
             Self::Auction => IdentTopic::new(Self::AUCTION),
             Self::PendingTx => IdentTopic::new(Self::PENDING_TX),
+            Self::PendingEscrow => IdentTopic::new(Self::PENDING_ESCROW),
             Self::ChainUpdate => IdentTopic::new(Self::CHAIN_UPDATE),
             Self::Client(peer) => IdentTopic::new(peer.to_string()),
+            Self::Capabilities => IdentTopic::new(Self::CAPABILITIES),
         }
     }
 }
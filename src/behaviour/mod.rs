@@ -1,5 +1,14 @@
+pub mod auction;
+pub mod bidding;
+pub mod capability;
+pub mod dht;
+pub mod encryption;
 pub mod gossip;
+pub mod pricing;
+pub mod receipt;
+pub mod rendezvous;
 pub mod req_res;
+pub mod status;
 pub mod streaming;
 use crate::util::heap::max::MaxHeapable;
 use libp2p::PeerId;
@@ -8,16 +17,54 @@ use std::cmp::Ordering;
 
 pub const IDENTIFY_ID: &str = "/id/1.0.0";
 
+/// Identifies an auction & the session opened with its winner
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AuctionId(pub u64);
+
+impl AuctionId {
+    pub fn random() -> Self {
+        Self(rand::random())
+    }
+}
+
+impl std::fmt::Display for AuctionId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+/// Parses the hex form ids are displayed in
+impl std::str::FromStr for AuctionId {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        u64::from_str_radix(s, 16).map(Self)
+    }
+}
+
 /// Sent by provider to request that it provide to client
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub struct ProvisionBid {
     pub peer: PeerId,
     // distance: u64,
+    /// Price asked per token
     pub bid: f64,
+    /// Share of the provider's session slots in use when it bid, above 1 when jobs are queued
+    #[serde(default)]
+    pub load: f32,
+    /// Tokens per second the provider has been measured generating, if it has served a session
+    #[serde(default)]
+    pub throughput: Option<f32>,
+    /// Auction the bid was made in, every bid would otherwise look the same to gossip
+    #[serde(default)]
+    pub auction: Option<AuctionId>,
+    /// Milliseconds since the unix epoch after which the provider no longer holds to the bid
+    #[serde(default)]
+    pub expires: u64,
 }
 
 impl MaxHeapable for ProvisionBid {}
-/// Should be changed later to account for many other factors
+/// Orders by price only, clients rank bids with a `bidding::BidStrategy`
 impl PartialOrd for ProvisionBid {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         self.bid.partial_cmp(&other.bid)
@@ -36,12 +83,34 @@ impl ProvisionBid {
             peer,
             // distance,
             bid,
+            load: 0.,
+            throughput: None,
+            auction: None,
+            expires: 0,
         }
     }
 
-    pub fn better_than(&self, other: &Self) -> bool {
-        // should include a distance based weight
-        // should also have computer speed info
-        self.bid > other.bid
+    pub fn for_auction(mut self, auction: AuctionId) -> Self {
+        self.auction = Some(auction);
+        self
+    }
+
+    pub fn with_load(mut self, load: f32) -> Self {
+        self.load = load;
+        self
+    }
+
+    pub fn with_throughput(mut self, tokens_per_sec: f32) -> Self {
+        self.throughput = Some(tokens_per_sec);
+        self
+    }
+
+    pub fn expiring(mut self, at_millis: u64) -> Self {
+        self.expires = at_millis;
+        self
+    }
+
+    pub fn expired(&self, now_millis: u64) -> bool {
+        now_millis > self.expires
     }
 }
@@ -0,0 +1,78 @@
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, path::Path};
+
+use super::auction::AuctionTerms;
+use crate::{inference::jobs::Load, MainResult};
+
+/// How a provider prices its bids, every price is per input or output token
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PricingPolicy {
+    /// Rate of models missing from `rates`
+    pub default_rate: f64,
+    pub rates: HashMap<String, f64>,
+    /// Share of the rate added for every session waiting in the queue
+    pub queue_markup: f64,
+    /// Least charged for a whole job, raising the price of jobs with few tokens
+    pub min_job_price: f64,
+    /// No bid is ever lower
+    pub floor: f64,
+}
+
+impl Default for PricingPolicy {
+    fn default() -> Self {
+        Self {
+            default_rate: 5.,
+            rates: HashMap::new(),
+            queue_markup: 0.,
+            min_job_price: 0.,
+            floor: 0.,
+        }
+    }
+}
+
+impl PricingPolicy {
+    /// Reads a policy from a json file, fields that are left out keep their defaults
+    pub fn load(path: impl AsRef<Path>) -> MainResult<Self> {
+        let policy: Self = serde_json::from_slice(&std::fs::read(path)?)?;
+        policy.validate()?;
+        Ok(policy)
+    }
+
+    pub fn validate(&self) -> MainResult<()> {
+        let prices = [
+            self.default_rate,
+            self.queue_markup,
+            self.min_job_price,
+            self.floor,
+        ];
+        let valid = |price: &f64| price.is_finite() && *price >= 0.;
+        if !prices.iter().chain(self.rates.values()).all(valid) {
+            return Err("prices must be finite & not negative".into());
+        }
+        Ok(())
+    }
+
+    pub fn rate(&self, model: &str) -> f64 {
+        self.rates.get(model).copied().unwrap_or(self.default_rate)
+    }
+
+    /// Lowest & highest price per token bid on `models` with up to `max_queued` sessions
+    /// queued, the job minimum can raise the price of small jobs above it
+    pub fn range(&self, models: &[String], max_queued: usize) -> (f64, f64) {
+        let rates = models.iter().map(|model| self.rate(model));
+        let lowest = rates.clone().fold(f64::INFINITY, f64::min);
+        let highest = rates.fold(0., f64::max) * (1. + self.queue_markup * max_queued as f64);
+        match models.is_empty() {
+            true => (self.floor, self.floor),
+            false => (lowest.max(self.floor), highest.max(self.floor)),
+        }
+    }
+
+    /// Price per token to bid on the job `terms` describe while under `load`
+    pub fn price(&self, terms: &AuctionTerms, load: &Load) -> f64 {
+        let marked_up = self.rate(&terms.model) * (1. + self.queue_markup * load.queued as f64);
+        let job_minimum = self.min_job_price / terms.total_tokens().max(1) as f64;
+        marked_up.max(job_minimum).max(self.floor)
+    }
+}
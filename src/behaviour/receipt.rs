@@ -0,0 +1,173 @@
+use libp2p::{
+    identity::{Keypair, PublicKey},
+    PeerId,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use super::{streaming::Usage, AuctionId};
+use crate::{util::PublicKeyBytes, MainResult};
+
+/// File receipts are kept in, within the data directory
+const RECEIPTS_FILE: &str = "receipts.json";
+
+/// What a provider did in a session, the basis for payment & disputes
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Receipt {
+    pub auction: AuctionId,
+    pub client: PeerId,
+    pub provider: PeerId,
+    pub usage: Usage,
+    pub price_per_token: f64,
+    /// Sha256 of the output streamed in the session
+    pub output_hash: [u8; 32],
+}
+
+/// Signed by the provider at the end of a session & countersigned by the client once it has
+/// checked it against what it received
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct SignedReceipt {
+    pub receipt: Receipt,
+    provider_key: PublicKeyBytes,
+    provider_signature: Vec<u8>,
+    client_key: Option<PublicKeyBytes>,
+    client_signature: Option<Vec<u8>>,
+}
+
+impl Receipt {
+    pub fn hash_output(output: &str) -> [u8; 32] {
+        Sha256::digest(output.as_bytes()).into()
+    }
+
+    /// Hex sha256 of the receipt, how payments refer to it
+    pub fn digest(&self) -> MainResult<String> {
+        Ok(format!("{:x}", Sha256::digest(serde_json::to_vec(self)?)))
+    }
+
+    /// What the client owes for the session
+    pub fn cost(&self) -> f64 {
+        (self.usage.input_tokens + self.usage.output_tokens) as f64 * self.price_per_token
+    }
+}
+
+/// Errors unless `signature` over `receipt` is by `key` & `key` belongs to `signer`
+fn check_signature(
+    receipt: &Receipt,
+    key: &PublicKeyBytes,
+    signature: &[u8],
+    signer: &PeerId,
+) -> MainResult<()> {
+    let key: PublicKey = key.try_into()?;
+    if key.to_peer_id() != *signer {
+        return Err(format!("receipt for {signer} is signed by {}", key.to_peer_id()).into());
+    }
+    if !key.verify(&serde_json::to_vec(receipt)?, signature) {
+        return Err("receipt has an invalid signature".into());
+    }
+    Ok(())
+}
+
+impl SignedReceipt {
+    /// Signs `receipt` as its provider
+    pub fn new(receipt: Receipt, keys: &Keypair) -> MainResult<Self> {
+        if keys.public().to_peer_id() != receipt.provider {
+            return Err("only the provider can issue a receipt".into());
+        }
+        let provider_signature = keys.sign(&serde_json::to_vec(&receipt)?)?;
+        Ok(Self {
+            receipt,
+            provider_key: keys.public().into(),
+            provider_signature,
+            client_key: None,
+            client_signature: None,
+        })
+    }
+
+    /// Signs the receipt as its client, agreeing to what it says
+    pub fn countersign(&mut self, keys: &Keypair) -> MainResult<()> {
+        if keys.public().to_peer_id() != self.receipt.client {
+            return Err("only the client can countersign a receipt".into());
+        }
+        self.verify()?;
+        self.client_signature = Some(keys.sign(&serde_json::to_vec(&self.receipt)?)?);
+        self.client_key = Some(keys.public().into());
+        Ok(())
+    }
+
+    pub fn provider_key(&self) -> &PublicKeyBytes {
+        &self.provider_key
+    }
+
+    pub fn countersigned(&self) -> bool {
+        self.client_signature.is_some()
+    }
+
+    /// Errors unless the provider signed the receipt & the client did too, if it countersigned
+    pub fn verify(&self) -> MainResult<()> {
+        let receipt = &self.receipt;
+        check_signature(
+            receipt,
+            &self.provider_key,
+            &self.provider_signature,
+            &receipt.provider,
+        )?;
+        match (&self.client_key, &self.client_signature) {
+            (Some(key), Some(signature)) => {
+                check_signature(receipt, key, signature, &receipt.client)
+            }
+            (None, None) => Ok(()),
+            _ => Err("receipt is missing the client's key or signature".into()),
+        }
+    }
+}
+
+/// Receipts of every session we took part in, persisted in the data directory
+#[derive(Debug, Default)]
+pub struct Receipts {
+    /// None when running without a data directory, nothing is persisted
+    path: Option<PathBuf>,
+    receipts: HashMap<AuctionId, SignedReceipt>,
+}
+
+impl Receipts {
+    /// Loads the receipts kept in `data_dir`, starting with none if there are none yet
+    pub fn load(data_dir: Option<&Path>) -> MainResult<Self> {
+        let path = data_dir.map(|dir| dir.join(RECEIPTS_FILE));
+        let receipts = match path.as_ref().filter(|path| path.exists()) {
+            Some(path) => serde_json::from_slice(&std::fs::read(path)?)?,
+            None => HashMap::new(),
+        };
+        Ok(Self { path, receipts })
+    }
+
+    /// Keeps `receipt` once it has been verified, replacing the one kept for its auction.
+    /// Receipts are rare, every one is saved straight away
+    pub fn insert(&mut self, receipt: SignedReceipt) -> MainResult<()> {
+        receipt.verify()?;
+        self.receipts.insert(receipt.receipt.auction, receipt);
+        let Some(path) = self.path.as_ref() else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_vec_pretty(&self.receipts)?)?;
+        Ok(())
+    }
+
+    pub fn get(&self, auction: &AuctionId) -> Option<&SignedReceipt> {
+        self.receipts.get(auction)
+    }
+
+    pub fn len(&self) -> usize {
+        self.receipts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.receipts.is_empty()
+    }
+}
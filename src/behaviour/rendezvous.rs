@@ -0,0 +1,180 @@
+use libp2p::{
+    rendezvous::{self, client::RegisterError, Cookie, Namespace, Ttl},
+    PeerId,
+};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use crate::MainResult;
+
+/// TTL servers ask for when registering, this is the rendezvous server's minimum
+pub const REGISTRATION_TTL: Ttl = rendezvous::MIN_TTL;
+/// How long before a registration expires that it is refreshed
+const REFRESH_MARGIN: Duration = Duration::from_secs(60 * 10);
+/// How long to wait before trying again after a failed registration
+const REGISTER_RETRY: Duration = Duration::from_secs(30);
+/// How often clients ask the boot node for newly registered peers
+const DISCOVERY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Namespaces nodes register under at the boot node, keyed by role
+pub enum RoleNamespace<'n> {
+    /// Providers register once for every model they serve
+    Providers(&'n str),
+    /// All miners share a single namespace
+    Miners,
+}
+
+impl<'n> RoleNamespace<'n> {
+    const PROVIDERS: &'n str = "providers";
+    const MINERS: &'n str = "miners";
+    pub fn namespace(&self) -> Namespace {
+        let raw = match self {
+            Self::Providers(model) => format!("{}/{model}", Self::PROVIDERS),
+            Self::Miners => Self::MINERS.to_string(),
+        };
+        Namespace::new(raw).expect("role namespace too long")
+    }
+}
+
+/// Keeps a node's registrations at a rendezvous node alive
+#[derive(Debug)]
+pub struct Registrar {
+    namespaces: Vec<Namespace>,
+    refresh_at: Option<Instant>,
+}
+
+impl Registrar {
+    pub fn new<'n>(namespaces: impl IntoIterator<Item = RoleNamespace<'n>>) -> Self {
+        Self {
+            namespaces: namespaces.into_iter().map(|ns| ns.namespace()).collect(),
+            refresh_at: None,
+        }
+    }
+
+    /// Register every namespace with the given rendezvous node
+    pub fn register(
+        &mut self,
+        behaviour: &mut rendezvous::client::Behaviour,
+        rendezvous_node: PeerId,
+    ) -> MainResult<()> {
+        for namespace in self.namespaces.iter() {
+            match behaviour.register(namespace.clone(), rendezvous_node, Some(REGISTRATION_TTL)) {
+                Ok(()) => {}
+                Err(RegisterError::NoExternalAddresses) => {
+                    tracing::warn!("no external addresses yet, retrying registration later");
+                    self.refresh_at = Some(Instant::now() + REGISTER_RETRY);
+                    return Ok(());
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Should be called with every rendezvous client event the node receives
+    pub fn handle_event(&mut self, event: &rendezvous::client::Event) {
+        match event {
+            rendezvous::client::Event::Registered { ttl, namespace, .. } => {
+                tracing::info!("registered under {namespace} for {ttl}s");
+                let refresh_in = Duration::from_secs(*ttl).saturating_sub(REFRESH_MARGIN);
+                let refresh_at = Instant::now() + refresh_in;
+                self.refresh_at = Some(
+                    self.refresh_at
+                        .map_or(refresh_at, |current| current.min(refresh_at)),
+                );
+            }
+            rendezvous::client::Event::RegisterFailed {
+                namespace, error, ..
+            } => {
+                tracing::error!("failed to register under {namespace}: {error:?}");
+                self.refresh_at = Some(Instant::now() + REGISTER_RETRY);
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns true once if registrations are due to be refreshed
+    pub fn refresh_due(&mut self) -> bool {
+        if self.refresh_at.is_some_and(|at| Instant::now() >= at) {
+            self.refresh_at = None;
+            return true;
+        }
+        false
+    }
+}
+
+/// Periodically asks a rendezvous node for peers registered under some namespaces
+#[derive(Debug)]
+pub struct Discoverer {
+    cookies: HashMap<Namespace, Option<Cookie>>,
+    next_at: Option<Instant>,
+}
+
+impl Discoverer {
+    pub fn new<'n>(namespaces: impl IntoIterator<Item = RoleNamespace<'n>>) -> Self {
+        Self {
+            cookies: namespaces
+                .into_iter()
+                .map(|ns| (ns.namespace(), None))
+                .collect(),
+            next_at: None,
+        }
+    }
+
+    /// Discover every namespace, only asking for registrations we have not yet seen
+    pub fn discover(
+        &mut self,
+        behaviour: &mut rendezvous::client::Behaviour,
+        rendezvous_node: PeerId,
+    ) {
+        for (namespace, cookie) in self.cookies.iter() {
+            behaviour.discover(
+                Some(namespace.clone()),
+                cookie.clone(),
+                None,
+                rendezvous_node,
+            );
+        }
+        self.next_at = Some(Instant::now() + DISCOVERY_INTERVAL);
+    }
+
+    /// Stores the returned cookie and returns every registered peer along with its addresses
+    pub fn handle_event(
+        &mut self,
+        event: rendezvous::client::Event,
+    ) -> Vec<(PeerId, Vec<libp2p::Multiaddr>)> {
+        match event {
+            rendezvous::client::Event::Discovered {
+                registrations,
+                cookie,
+                ..
+            } => {
+                if let Some(namespace) = cookie.namespace() {
+                    self.cookies.insert(namespace.clone(), Some(cookie.clone()));
+                }
+                registrations
+                    .into_iter()
+                    .map(|r| (r.record.peer_id(), r.record.addresses().to_vec()))
+                    .collect()
+            }
+            rendezvous::client::Event::DiscoverFailed {
+                namespace, error, ..
+            } => {
+                tracing::error!("failed to discover {namespace:?}: {error:?}");
+                vec![]
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Returns true once if it is time to discover again
+    pub fn discovery_due(&mut self) -> bool {
+        if self.next_at.is_some_and(|at| Instant::now() >= at) {
+            self.next_at = None;
+            return true;
+        }
+        false
+    }
+}
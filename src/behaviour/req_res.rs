@@ -1,3 +1,4 @@
+use super::status::Status;
 use serde::{Deserialize, Serialize};
 
 pub type NetworkReqRes = libp2p::request_response::json::Behaviour<NetworkRequest, NetworkResponse>;
@@ -6,6 +7,8 @@ pub type NetworkReqRes = libp2p::request_response::json::Behaviour<NetworkReques
 pub enum NetworkRequest {
     /// Client requests server starts listening on stream
     OpenStream,
+    /// Sent upon connecting, carries the sender's status
+    Status(Status),
     // Chain,
 }
 
@@ -13,5 +16,7 @@ pub enum NetworkRequest {
 pub enum NetworkResponse {
     /// provider lets client know that it has started listening,
     OpenStreamAck { opened: bool },
+    /// Responds to a status request with our own status
+    Status(Status),
     // Chain(Blockchain),
 }
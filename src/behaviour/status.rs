@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+
+use crate::MainResult;
+
+/// What a node does on the network
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum NodeRole {
+    Provider,
+    Miner,
+    Client,
+}
+
+/// Exchanged by both sides of every new connection, peers on a different network are dropped
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Status {
+    pub chain_id: String,
+    pub genesis_hash: String,
+    pub protocol_version: String,
+    /// Index of the last block in the sender's chain
+    pub tip_height: u64,
+    pub role: NodeRole,
+}
+
+impl Status {
+    /// Errors with the reason `other` is not on the same network as us
+    pub fn check_compatible(&self, other: &Self) -> MainResult<()> {
+        if self.chain_id != other.chain_id {
+            return Err(format!("chain id {} is not {}", other.chain_id, self.chain_id).into());
+        }
+        if self.genesis_hash != other.genesis_hash {
+            return Err(format!("genesis hash {} is not ours", other.genesis_hash).into());
+        }
+        if self.protocol_version != other.protocol_version {
+            return Err(format!(
+                "protocol version {} is not {}",
+                other.protocol_version, self.protocol_version
+            )
+            .into());
+        }
+        Ok(())
+    }
+}
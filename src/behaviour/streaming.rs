@@ -1,95 +1,658 @@
-use std::time::Duration;
+use std::{io, pin::pin, time::Duration};
 
-use futures::{AsyncReadExt, AsyncWriteExt};
-use libp2p::{PeerId, Stream, StreamProtocol};
-use rand::RngCore;
-use serde::{Deserialize, Serialize};
+use futures::{
+    channel::oneshot,
+    future::{self, Either, FusedFuture},
+    io::{ReadHalf, WriteHalf},
+    select, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, FutureExt, StreamExt,
+};
+use futures_timer::Delay;
+use libp2p::{identity::Keypair, PeerId, Stream, StreamProtocol};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
-pub const STREAM_PROTOCOL: StreamProtocol = StreamProtocol::new("/echo");
+use super::{
+    encryption::{Handshake, PendingHandshake, SessionCipher, Side},
+    receipt::{Receipt, SignedReceipt},
+    AuctionId,
+};
+use crate::{inference::InferenceBackend, MainResult};
 
-/// https://github.com/libp2p/rust-libp2p/blob/master/examples/stream/src/main.rs
-#[derive(Debug, Deserialize, Serialize)]
+/// Bumped whenever `StreamMessage` changes in a way older nodes can't understand
+pub const INFERENCE_PROTOCOL: StreamProtocol = StreamProtocol::new("/inference/4.0.0");
+/// Largest frame either side will read, anything bigger is treated as a protocol error
+pub const MAX_FRAME_SIZE: usize = 1024 * 1024;
+/// Bytes used by the length prefix of every frame
+const LENGTH_PREFIX_SIZE: usize = size_of::<u32>();
+/// Most bytes `MessageStream::recv` reads at once
+const READ_CHUNK_SIZE: usize = 8 * 1024;
+/// Longest a provider goes without sending anything while it generates
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+/// Longest a client waits for a message before treating the session as dead
+pub const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Everything sent over an inference session, encrypted once both sides have exchanged a
+/// `Handshake`.
+/// The client opens the session & sends a prompt, the provider streams back chunks of the
+/// completion followed by a receipt of its usage, which the client countersigns
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub enum StreamMessage {
-    Open,
-    Content(String),
-    Close,
+    Open(SessionOpen),
+    Prompt(String),
+    /// Incremental output of the completion
+    Chunk(String),
+    /// Ends a session, also after the client cancels it. Signed by the provider
+    Receipt(SignedReceipt),
+    /// The client's reply to `Receipt` once it agrees with it
+    Countersigned(SignedReceipt),
+    Error(String),
+    /// Either side abandons the session, a provider stops generating & reports its usage
+    Cancel,
+    /// Sent by the provider when it has had nothing else to send for `HEARTBEAT_INTERVAL`
+    Heartbeat,
 }
-const MESSAGE_SIZE: usize = size_of::<StreamMessage>() + 1;
 
-/// A very simple, `async fn`-based connection handler for our custom echo protocol.
-pub async fn connection_handler(peer: PeerId, mut control: libp2p_stream::Control) {
-    loop {
-        tokio::time::sleep(Duration::from_secs(1)).await; // Wait a second between echos.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct SessionOpen {
+    /// The auction the provider won this session in
+    pub auction: AuctionId,
+    pub model: String,
+    pub params: InferenceParams,
+    /// Price of the bid that won the auction, the provider charges no more than it
+    pub price_per_token: f64,
+    /// Output streamed by a provider whose session died, the completion picks up after it
+    #[serde(default)]
+    pub resume: Option<String>,
+}
 
-        let stream = match control.open_stream(peer, STREAM_PROTOCOL).await {
-            Ok(stream) => stream,
-            Err(error @ libp2p_stream::OpenStreamError::UnsupportedProtocol(_)) => {
-                tracing::info!(%peer, %error);
-                return;
-            }
-            Err(error) => {
-                // Other errors may be temporary.
-                // In production, something like an exponential backoff / circuit-breaker may be
-                // more appropriate.
-                tracing::debug!(%peer, %error);
-                continue;
-            }
-        };
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct InferenceParams {
+    pub max_tokens: u32,
+    pub temperature: f32,
+}
 
-        if let Err(e) = send(stream).await {
-            tracing::error!(%peer, "Echo send failed: {e}");
-            continue;
+impl Default for InferenceParams {
+    fn default() -> Self {
+        Self {
+            max_tokens: 256,
+            temperature: 0.7,
         }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Usage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+/// Everything a provider returned over a finished session
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completion {
+    pub content: String,
+    /// Countersigned receipt of the session
+    pub receipt: SignedReceipt,
+    /// Whether the client stopped the session early, the receipt covers what was produced until
+    /// then
+    pub cancelled: bool,
+}
 
-        tracing::info!(%peer, "Echo complete!")
+impl Completion {
+    pub fn usage(&self) -> Usage {
+        self.receipt.receipt.usage
     }
 }
 
-// this should eventually do inference
+/// Output a client has been streamed so far, kept across sessions so another provider can
+/// resume a completion where a failed one left off
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Progress {
+    pub content: String,
+    pub output_tokens: u64,
+}
 
-pub async fn echo(mut stream: Stream) -> std::io::Result<()> {
-    // let mut total = 0;
-    let mut buf = [0u8; MESSAGE_SIZE];
+/// Writes `message` as json, prefixed with its length as a big endian u32
+pub async fn write_frame<W, T>(io: &mut W, message: &T) -> io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+    T: Serialize,
+{
+    write_frame_bytes(io, &serde_json::to_vec(message)?).await
+}
 
-    loop {
-        let n = stream.read(&mut buf).await?;
-        if n == 0 {
-            return Ok(());
+/// Reads a single frame written by `write_frame`.
+/// Returns None if the other side closed the stream between frames
+pub async fn read_frame<R, T>(io: &mut R) -> io::Result<Option<T>>
+where
+    R: AsyncRead + Unpin,
+    T: DeserializeOwned,
+{
+    match read_frame_bytes(io).await? {
+        Some(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+        None => Ok(None),
+    }
+}
+
+/// Writes `bytes` prefixed with their length as a big endian u32
+pub async fn write_frame_bytes<W>(io: &mut W, bytes: &[u8]) -> io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    if bytes.len() > MAX_FRAME_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("frame of {} bytes exceeds {MAX_FRAME_SIZE}", bytes.len()),
+        ));
+    }
+    io.write_all(&(bytes.len() as u32).to_be_bytes()).await?;
+    io.write_all(bytes).await?;
+    io.flush().await
+}
+
+/// Removes the first frame from `buf` once all of it has been read
+fn take_frame(buf: &mut Vec<u8>) -> io::Result<Option<Vec<u8>>> {
+    let Some(prefix) = buf.get(..LENGTH_PREFIX_SIZE) else {
+        return Ok(None);
+    };
+    let len = u32::from_be_bytes(prefix.try_into().expect("prefix is 4 bytes")) as usize;
+    if len > MAX_FRAME_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("frame of {len} bytes exceeds {MAX_FRAME_SIZE}"),
+        ));
+    }
+    if buf.len() < LENGTH_PREFIX_SIZE + len {
+        return Ok(None);
+    }
+    let frame = buf[LENGTH_PREFIX_SIZE..LENGTH_PREFIX_SIZE + len].to_vec();
+    buf.drain(..LENGTH_PREFIX_SIZE + len);
+    Ok(Some(frame))
+}
+
+/// Reads the bytes of a single frame written by `write_frame_bytes`.
+/// Returns None if the other side closed the stream between frames
+pub async fn read_frame_bytes<R>(io: &mut R) -> io::Result<Option<Vec<u8>>>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut prefix = [0u8; LENGTH_PREFIX_SIZE];
+    let mut read = 0;
+    while read < LENGTH_PREFIX_SIZE {
+        match io.read(&mut prefix[read..]).await? {
+            0 if read == 0 => return Ok(None),
+            0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+            n => read += n,
+        }
+    }
+
+    let len = u32::from_be_bytes(prefix) as usize;
+    if len > MAX_FRAME_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("frame of {len} bytes exceeds {MAX_FRAME_SIZE}"),
+        ));
+    }
+    let mut buf = vec![0u8; len];
+    io.read_exact(&mut buf).await?;
+    Ok(Some(buf))
+}
+
+/// Sends & receives `StreamMessage`s over a stream, encrypting them after a `handshake`
+pub struct MessageStream<S = Stream> {
+    stream: S,
+    cipher: Option<SessionCipher>,
+    /// Bytes read past the last whole frame, kept so a dropped `recv` loses nothing
+    received: Vec<u8>,
+}
+
+impl<S> MessageStream<S> {
+    /// Messages are sent in the clear until `handshake` is called
+    pub fn new(stream: S) -> Self {
+        Self {
+            stream,
+            cipher: None,
+            received: vec![],
+        }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+impl<S> MessageStream<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    /// Agrees on keys for a session in `auction` with the provider `peer`, every message sent
+    /// afterwards is encrypted
+    pub async fn handshake(
+        &mut self,
+        keys: &Keypair,
+        peer: &PeerId,
+        auction: AuctionId,
+    ) -> MainResult<()> {
+        let pending = PendingHandshake::new(keys, Side::Client, auction)?;
+        write_frame(&mut self.stream, pending.handshake()).await?;
+        let theirs = Self::read_handshake(&mut self.stream).await?;
+        self.cipher = Some(pending.finish(&theirs, peer)?);
+        Ok(())
+    }
+
+    /// Provider side of `handshake`, the client picks the auction & `accept` decides whether
+    /// it may open a session in it
+    pub async fn accept_handshake(
+        &mut self,
+        keys: &Keypair,
+        peer: &PeerId,
+        accept: impl FnOnce(AuctionId) -> bool,
+    ) -> MainResult<AuctionId> {
+        let theirs = Self::read_handshake(&mut self.stream).await?;
+        let auction = theirs.auction();
+        if !accept(auction) {
+            return Err(format!("{peer} can't open a session in auction {auction}").into());
+        }
+        let pending = PendingHandshake::new(keys, Side::Provider, auction)?;
+        write_frame(&mut self.stream, pending.handshake()).await?;
+        self.cipher = Some(pending.finish(&theirs, peer)?);
+        Ok(auction)
+    }
+
+    async fn read_handshake(stream: &mut S) -> MainResult<Handshake> {
+        let handshake = read_frame(stream)
+            .await?
+            .ok_or("stream closed during the handshake")?;
+        Ok(handshake)
+    }
+
+    /// Splits into a half that receives & one that sends, so messages can be waited on while
+    /// others are sent. Each half only uses the cipher's counter for its own direction
+    pub fn split(self) -> (MessageStream<ReadHalf<S>>, MessageStream<WriteHalf<S>>) {
+        let (reading, writing) = AsyncReadExt::split(self.stream);
+        let receiving = MessageStream {
+            stream: reading,
+            cipher: self.cipher.clone(),
+            received: self.received,
+        };
+        let sending = MessageStream {
+            stream: writing,
+            cipher: self.cipher,
+            received: vec![],
+        };
+        (receiving, sending)
+    }
+}
+
+impl<S> MessageStream<S>
+where
+    S: AsyncWrite + Unpin,
+{
+    pub async fn send(&mut self, message: &StreamMessage) -> io::Result<()> {
+        let mut bytes = serde_json::to_vec(message)?;
+        if let Some(cipher) = self.cipher.as_mut() {
+            bytes = cipher.encrypt(&bytes).map_err(io::Error::other)?;
         }
-        // let read = stream.read(&mut buf).await?;
-        // let val = serde_json::from_reader(stream)?;
+        write_frame_bytes(&mut self.stream, &bytes).await
+    }
 
-        // total += read;
-        let raw = String::from_utf8_lossy(&buf[..n]);
-        tracing::warn!("raw: {raw}");
-        let val: StreamMessage = serde_json::from_slice(&buf[..n])?;
-        tracing::warn!("received {val:?} in echo receive");
-        let bytes = serde_json::to_vec(&val).unwrap();
-        stream.write_all(&bytes).await.expect("failed to write");
+    pub async fn close(&mut self) -> io::Result<()> {
+        self.stream.close().await
     }
 }
 
-async fn send(mut stream: Stream) -> std::io::Result<()> {
-    let m = StreamMessage::Content("Hello World".to_string());
-    let bytes = serde_json::to_vec(&m).unwrap();
+impl<S> MessageStream<S>
+where
+    S: AsyncRead + Unpin,
+{
+    /// None once the other side has closed the stream.
+    /// Cancel safe, a frame that was partly read when the future was dropped is finished by
+    /// the next call
+    pub async fn recv(&mut self) -> io::Result<Option<StreamMessage>> {
+        let Some(mut bytes) = self.next_frame().await? else {
+            return Ok(None);
+        };
+        if let Some(cipher) = self.cipher.as_mut() {
+            bytes = cipher
+                .decrypt(&bytes)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        }
+        Ok(Some(serde_json::from_slice(&bytes)?))
+    }
 
-    // let mut bytes = vec![0; MESSAGE_SIZE];
-    // rand::thread_rng().fill_bytes(&mut bytes);
+    async fn next_frame(&mut self) -> io::Result<Option<Vec<u8>>> {
+        loop {
+            if let Some(frame) = take_frame(&mut self.received)? {
+                return Ok(Some(frame));
+            }
+            let mut chunk = [0u8; READ_CHUNK_SIZE];
+            match self.stream.read(&mut chunk).await? {
+                0 if self.received.is_empty() => return Ok(None),
+                0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+                n => self.received.extend_from_slice(&chunk[..n]),
+            }
+        }
+    }
 
-    let mut buf = [0u8; MESSAGE_SIZE];
-    stream.write_all(&bytes).await.expect("write failed");
+    /// Like `recv`, but errors if nothing arrives within `timeout`
+    pub async fn recv_within(&mut self, timeout: Duration) -> MainResult<Option<StreamMessage>> {
+        match future::select(pin!(self.recv()), Delay::new(timeout)).await {
+            Either::Left((message, _)) => Ok(message?),
+            Either::Right(_) => Err(format!("nothing was received for {timeout:?}").into()),
+        }
+    }
+}
 
-    let n = stream.read(&mut buf).await.expect("read failed");
-    // let raw = String::from_utf8_lossy(&buf[..n]);
-    // tracing::warn!("raw: {raw}");
-    let val: StreamMessage = serde_json::from_slice(&buf[..n])?;
-    tracing::warn!("received {val:?} in echo send");
+/// Opens an inference session with `provider` & returns the completion of `prompt`
+pub async fn request_completion(
+    mut control: libp2p_stream::Control,
+    keys: Keypair,
+    provider: PeerId,
+    open: SessionOpen,
+    prompt: String,
+    progress: &mut Progress,
+    cancel: oneshot::Receiver<()>,
+) -> MainResult<Completion> {
+    let stream = control.open_stream(provider, INFERENCE_PROTOCOL).await?;
+    let stream = MessageStream::new(stream);
+    run_session(stream, &keys, &provider, open, prompt, progress, cancel).await
+}
 
-    // if bytes != buf {
-    //     return Err(std::io::Error::other("incorrect echo"));
-    // }
+/// Client side of a session, sends the prompt & collects chunks into `progress` until the
+/// provider sends a receipt, which is countersigned once it matches what was received.
+/// The session is dead if the provider goes quiet for `HEARTBEAT_TIMEOUT`.
+/// Once `cancel` fires the provider is told to stop & the output produced until it did is
+/// returned, dropping its sender doesn't cancel
+pub async fn run_session<S>(
+    mut stream: MessageStream<S>,
+    keys: &Keypair,
+    provider: &PeerId,
+    open: SessionOpen,
+    prompt: String,
+    progress: &mut Progress,
+    mut cancel: oneshot::Receiver<()>,
+) -> MainResult<Completion>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    stream.handshake(keys, provider, open.auction).await?;
+    stream.send(&StreamMessage::Open(open.clone())).await?;
+    stream.send(&StreamMessage::Prompt(prompt.clone())).await?;
 
+    let resumed = progress.clone();
+    let (mut incoming, mut stream) = stream.split();
+    let (mut receipt, cancelled) = {
+        let mut receiving = pin!(receive_completion(&mut incoming, progress).fuse());
+        select! {
+            receipt = receiving => (receipt?, false),
+            cancelled = cancel => match cancelled {
+                Ok(()) => {
+                    stream.send(&StreamMessage::Cancel).await?;
+                    (receiving.await?, true)
+                }
+                Err(_) => (receiving.await?, false),
+            },
+        }
+    };
+    let expected = Receipt {
+        auction: open.auction,
+        client: keys.public().to_peer_id(),
+        provider: *provider,
+        usage: Usage {
+            input_tokens: count_tokens(&prompt),
+            output_tokens: progress.output_tokens - resumed.output_tokens,
+        },
+        price_per_token: receipt.receipt.price_per_token,
+        output_hash: Receipt::hash_output(&progress.content[resumed.content.len()..]),
+    };
+    let checked =
+        check_receipt(&receipt, &expected, &open).and_then(|()| receipt.countersign(keys));
+    if let Err(err) = checked {
+        stream.send(&StreamMessage::Error(err.to_string())).await?;
+        return Err(format!("provider sent a bad receipt: {err}").into());
+    }
+    stream
+        .send(&StreamMessage::Countersigned(receipt.clone()))
+        .await?;
     stream.close().await?;
+    Ok(Completion {
+        content: progress.content.clone(),
+        receipt,
+        cancelled,
+    })
+}
 
+/// Errors unless the provider signed `receipt` & it is what the client `expected`, at a price
+/// no higher than the session was opened at
+fn check_receipt(
+    receipt: &SignedReceipt,
+    expected: &Receipt,
+    open: &SessionOpen,
+) -> MainResult<()> {
+    receipt.verify()?;
+    if receipt.receipt.price_per_token > open.price_per_token {
+        return Err(format!(
+            "charged {} per token after bidding {}",
+            receipt.receipt.price_per_token, open.price_per_token
+        )
+        .into());
+    }
+    if receipt.receipt != *expected {
+        return Err(format!("receipt {:?} doesn't match the session", receipt.receipt).into());
+    }
     Ok(())
 }
+
+/// Tokens a provider bills a prompt as
+fn count_tokens(prompt: &str) -> u64 {
+    prompt.split_whitespace().count() as u64
+}
+
+/// Collects chunks into `progress` until the provider sends a receipt
+async fn receive_completion<S>(
+    stream: &mut MessageStream<S>,
+    progress: &mut Progress,
+) -> MainResult<SignedReceipt>
+where
+    S: AsyncRead + Unpin,
+{
+    loop {
+        match stream.recv_within(HEARTBEAT_TIMEOUT).await? {
+            Some(StreamMessage::Chunk(chunk)) => {
+                progress.content.push_str(&chunk);
+                progress.output_tokens += 1;
+            }
+            Some(StreamMessage::Heartbeat) => {}
+            Some(StreamMessage::Receipt(receipt)) => return Ok(receipt),
+            Some(StreamMessage::Error(err)) => return Err(format!("provider failed: {err}").into()),
+            Some(StreamMessage::Cancel) => return Err("provider cancelled the session".into()),
+            Some(other) => return Err(format!("unexpected {other:?} from provider").into()),
+            None => return Err("provider closed the stream before finishing".into()),
+        }
+    }
+}
+
+/// Provider side of a session opened by `client`, `accept` returns the price we bid in the
+/// auctions it may be opened in. Only `models` may be requested & completions come from
+/// `backend`.
+/// If the client cancels, or stops reading, the backend's stream is dropped & the usage so far
+/// is billed. Returns the session's receipt, countersigned unless the client refused to, or
+/// None if the client left before sending a prompt
+pub async fn serve_session<S>(
+    mut stream: MessageStream<S>,
+    keys: &Keypair,
+    client: &PeerId,
+    accept: impl FnOnce(AuctionId) -> Option<f64>,
+    models: &[String],
+    backend: &dyn InferenceBackend,
+) -> MainResult<Option<SignedReceipt>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut price_per_token = 0.;
+    let auction = stream
+        .accept_handshake(keys, client, |auction| match accept(auction) {
+            Some(price) => {
+                price_per_token = price;
+                true
+            }
+            None => false,
+        })
+        .await?;
+    let open = match stream.recv().await? {
+        Some(StreamMessage::Open(open)) => open,
+        other => return Err(format!("expected a session to be opened, got {other:?}").into()),
+    };
+    if open.auction != auction {
+        let err = format!("session for auction {auction} opened {}", open.auction);
+        stream.send(&StreamMessage::Error(err.clone())).await?;
+        return Err(err.into());
+    }
+    if open.price_per_token < price_per_token {
+        let err = format!("session opened at {} per token", open.price_per_token);
+        stream.send(&StreamMessage::Error(err.clone())).await?;
+        return Err(err.into());
+    }
+    if !models.contains(&open.model) {
+        let err = format!("{} is not served here", open.model);
+        stream.send(&StreamMessage::Error(err.clone())).await?;
+        return Err(err.into());
+    }
+    tracing::info!("serving {} for auction {}", open.model, open.auction);
+
+    let prompt = match stream.recv().await? {
+        Some(StreamMessage::Prompt(prompt)) => prompt,
+        Some(StreamMessage::Cancel) | None => return Ok(None),
+        other => return Err(format!("expected a prompt, got {other:?}").into()),
+    };
+
+    let tokens = match &open.resume {
+        Some(output) => backend.resume(&open.model, &prompt, output, &open.params),
+        None => backend.complete(&open.model, &prompt, &open.params),
+    };
+    let tokens = match tokens {
+        Ok(tokens) => tokens,
+        Err(err) => {
+            stream
+                .send(&StreamMessage::Error("inference failed".into()))
+                .await?;
+            return Err(err);
+        }
+    };
+    let (mut incoming, mut stream) = stream.split();
+    let mut tokens = tokens.take(open.params.max_tokens as usize).fuse();
+    let mut output = String::new();
+    let mut output_tokens = 0;
+    // Scoped so the client can be read from again once it stops watching for a cancel
+    let cancelled = {
+        let mut cancelled = pin!(wait_for_cancel(&mut incoming).fuse());
+        loop {
+            let token = select! {
+                token = tokens.next() => match token {
+                    Some(token) => token,
+                    None => break,
+                },
+                _ = Delay::new(HEARTBEAT_INTERVAL).fuse() => {
+                    stream.send(&StreamMessage::Heartbeat).await?;
+                    continue;
+                }
+                _ = cancelled => {
+                    tracing::info!("client cancelled auction {auction} after {output_tokens} tokens");
+                    break;
+                }
+            };
+            match token {
+                Ok(token) => {
+                    stream.send(&StreamMessage::Chunk(token.clone())).await?;
+                    output.push_str(&token);
+                    output_tokens += 1;
+                }
+                Err(err) => {
+                    stream
+                        .send(&StreamMessage::Error("inference failed".into()))
+                        .await?;
+                    return Err(err);
+                }
+            }
+        }
+        cancelled.is_terminated()
+    };
+    // Stops the backend before the client is told how much it produced
+    drop(tokens);
+    let receipt = SignedReceipt::new(
+        Receipt {
+            auction,
+            client: *client,
+            provider: keys.public().to_peer_id(),
+            usage: Usage {
+                input_tokens: count_tokens(&prompt),
+                output_tokens,
+            },
+            price_per_token,
+            output_hash: Receipt::hash_output(&output),
+        },
+        keys,
+    )?;
+    if let Err(err) = stream.send(&StreamMessage::Receipt(receipt.clone())).await {
+        // A client that stopped reading still has to be billed for what it got
+        if !cancelled {
+            return Err(err.into());
+        }
+        return Ok(Some(receipt));
+    }
+    match recv_reply(&mut incoming, HEARTBEAT_TIMEOUT).await {
+        Ok(Some(StreamMessage::Countersigned(countersigned)))
+            if countersigned.receipt == receipt.receipt
+                && countersigned.countersigned()
+                && countersigned.verify().is_ok() =>
+        {
+            Ok(Some(countersigned))
+        }
+        Ok(Some(StreamMessage::Error(err))) => {
+            tracing::warn!("client refused the receipt for auction {auction}: {err}");
+            Ok(Some(receipt))
+        }
+        other => {
+            tracing::warn!("client didn't countersign auction {auction}: {other:?}");
+            Ok(Some(receipt))
+        }
+    }
+}
+
+/// The client's reply to a receipt, `Countersigned` or `Error`, unless it closes its side of
+/// the stream first. A `Cancel` the client sent as the output ended is skipped, like anything
+/// else `wait_for_cancel` ignores. Errors if no reply comes within `timeout`
+async fn recv_reply<S>(
+    stream: &mut MessageStream<S>,
+    timeout: Duration,
+) -> MainResult<Option<StreamMessage>>
+where
+    S: AsyncRead + Unpin,
+{
+    let reply =
+        async {
+            loop {
+                match stream.recv().await? {
+                    reply @ (Some(StreamMessage::Countersigned(_) | StreamMessage::Error(_))
+                    | None) => return io::Result::Ok(reply),
+                    Some(other) => tracing::debug!("ignoring {other:?} sent before the reply"),
+                }
+            }
+        };
+    match future::select(pin!(reply), Delay::new(timeout)).await {
+        Either::Left((reply, _)) => Ok(reply?),
+        Either::Right(_) => Err(format!("nothing was received for {timeout:?}").into()),
+    }
+}
+
+/// Resolves once the client cancels the session or closes its side of the stream.
+/// Dropping it doesn't lose a partly read message, see `MessageStream::recv`
+async fn wait_for_cancel<S>(stream: &mut MessageStream<S>)
+where
+    S: AsyncRead + Unpin,
+{
+    loop {
+        match stream.recv().await {
+            Ok(Some(StreamMessage::Cancel)) | Ok(None) | Err(_) => return,
+            Ok(Some(other)) => tracing::debug!("ignoring {other:?} sent mid-session"),
+        }
+    }
+}
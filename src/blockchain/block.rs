@@ -3,7 +3,7 @@ use crate::util::{
     map_vec::{Contains, MapVec},
 };
 
-use super::transaction::{mint::Mint, transfer::Transfer};
+use super::transaction::{escrow::Escrow, mint::Mint, transfer::Transfer};
 use libp2p::identity::{Keypair, PublicKey, SigningError};
 use serde::{Deserialize, Serialize};
 use sha3::Digest;
@@ -17,6 +17,9 @@ pub struct Block {
     nonce: u64,
     transfers: MapVec<String, Transfer>,
     mint: Mint,
+    /// Locks, claims & refunds of escrowed funds, applied in order
+    #[serde(default)]
+    escrows: Vec<Escrow>,
     signature: Vec<u8>,
 }
 
@@ -48,6 +51,7 @@ impl From<Block> for UnsignedBlock {
             nonce: value.nonce,
             transfers: value.transfers,
             mint: value.mint,
+            escrows: value.escrows,
         }
     }
 }
@@ -61,6 +65,7 @@ pub struct UnsignedBlock {
     pub nonce: u64,
     transfers: MapVec<String, Transfer>,
     mint: Mint,
+    escrows: Vec<Escrow>,
 }
 
 pub struct Fields<'h> {
@@ -70,6 +75,7 @@ pub struct Fields<'h> {
     nonce: &'h u64,
     transfers: &'h MapVec<String, Transfer>,
     mint: &'h Mint,
+    escrows: &'h [Escrow],
 }
 
 impl<'h> From<&'h Block> for Fields<'h> {
@@ -81,6 +87,7 @@ impl<'h> From<&'h Block> for Fields<'h> {
             nonce: &value.nonce,
             transfers: &value.transfers,
             mint: &value.mint,
+            escrows: &value.escrows,
         }
     }
 }
@@ -94,6 +101,7 @@ impl<'h> From<&'h UnsignedBlock> for Fields<'h> {
             nonce: &value.nonce,
             transfers: &value.transfers,
             mint: &value.mint,
+            escrows: &value.escrows,
         }
     }
 }
@@ -120,12 +128,17 @@ impl<'h> Hash<'h> for UnsignedBlock {
             fields.mint.valid(),
             "tried to hash a block with an invalid mint"
         );
+        assert!(
+            fields.escrows.iter().all(|e| e.valid()),
+            "tried to hash a block with invalid escrows"
+        );
         hasher.update(fields.index.to_string());
         hasher.update(fields.timestamp);
         hasher.update(fields.previous_hash);
         hasher.update(fields.nonce.to_string());
         Self::update_multiple(&mut hasher, fields.transfers.as_ref()).unwrap();
         hasher.update(fields.mint.hash_ref());
+        Self::update_multiple(&mut hasher, fields.escrows).unwrap();
         hasher.finalize()
     }
     fn hash_ref(&self) -> &str {
@@ -134,6 +147,31 @@ impl<'h> Hash<'h> for UnsignedBlock {
 }
 
 impl Block {
+    pub fn index(&self) -> u64 {
+        self.index
+    }
+
+    pub fn previous_hash(&self) -> &str {
+        &self.previous_hash
+    }
+
+    pub fn verify_signature(&self, key: &PublicKey) -> bool {
+        key.verify(self.hash.as_bytes(), &self.signature)
+    }
+
+    pub fn escrows(&self) -> &[Escrow] {
+        &self.escrows
+    }
+
+    /// Checks the transfers, mint & escrows before the block's own hash, as hashing a block
+    /// with invalid transactions panics
+    pub fn is_valid(&self) -> bool {
+        self.transfers.iter_vals().all(|t| t.valid())
+            && self.mint.valid()
+            && self.escrows.iter().all(|e| e.verify_signature())
+            && self.valid()
+    }
+
     /// Creates a new unsigned block & hashes
     pub fn new_unsigned(
         index: u64,
@@ -141,10 +179,28 @@ impl Block {
         previous_hash: String,
         transfers: impl Into<MapVec<String, Transfer>>,
         miner_key: PublicKey,
+    ) -> UnsignedBlock {
+        Self::new_unsigned_at(
+            index,
+            nonce,
+            previous_hash,
+            transfers,
+            miner_key,
+            crate::util::now_timestamp_string(),
+        )
+    }
+
+    /// Same as `new_unsigned`, but the block & its mint are timestamped with `timestamp`
+    pub fn new_unsigned_at(
+        index: u64,
+        nonce: u64,
+        previous_hash: String,
+        transfers: impl Into<MapVec<String, Transfer>>,
+        miner_key: PublicKey,
+        timestamp: String,
     ) -> UnsignedBlock {
         let transfers = Into::<MapVec<String, Transfer>>::into(transfers);
-        let mint = Mint::new(&transfers, miner_key);
-        let timestamp = crate::util::now_timestamp_string();
+        let mint = Mint::new_at(&transfers, miner_key, timestamp.clone());
         let fields = Fields {
             index: &index,
             timestamp: &timestamp,
@@ -152,6 +208,7 @@ impl Block {
             nonce: &nonce,
             transfers: &transfers,
             mint: &mint,
+            escrows: &[],
         };
         let hash = Self::output_to_string(Self::hash_fields(fields));
         UnsignedBlock {
@@ -162,11 +219,20 @@ impl Block {
             nonce,
             transfers,
             mint,
+            escrows: vec![],
         }
     }
 }
 
 impl UnsignedBlock {
+    /// Includes `escrows` in the block, applied in order after every earlier block's
+    pub fn with_escrows(mut self, escrows: Vec<Escrow>) -> Self {
+        self.escrows = escrows;
+        let fields = Fields::from(&self);
+        self.hash = Self::output_to_string(Self::hash_fields(fields));
+        self
+    }
+
     /// Mines the block using a Proof-of-Work mechanism.
     pub fn mine(&mut self, difficulty: usize) {
         let target = "0".repeat(difficulty);
@@ -188,6 +254,7 @@ impl UnsignedBlock {
             nonce: self.nonce,
             transfers: self.transfers,
             mint: self.mint,
+            escrows: self.escrows,
             signature,
         })
     }
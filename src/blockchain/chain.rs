@@ -1,14 +1,31 @@
-use super::block::Block;
-use crate::util::map_vec::MapVec;
-use libp2p::identity::Keypair;
+use super::{block::Block, ledger::Ledger, transaction::transfer::Transfer};
+use crate::{
+    util::{
+        hash::Hash,
+        map_vec::{Contains, MapVec},
+    },
+    MainResult,
+};
+use libp2p::{identity::Keypair, PeerId};
 use std::sync::LazyLock;
 
 pub type Blockchain = MapVec<String, Block>;
+/// Network nodes join unless configured otherwise
+pub const DEFAULT_CHAIN_ID: &str = "llm_chain";
+/// Fixed so that every node derives the same genesis block
+const GENESIS_TIMESTAMP: &str = "Thu, 1 Jan 1970 00:00:00 +0000";
 const GENESIS_BLOCK: LazyLock<Block> = LazyLock::new(|| {
     let k = BOOT_NODE_KEYPAIR;
     let keys = LazyLock::force(&k);
     // the need to use keys twice here is a little concerning.. might be fine tho idk
-    let block = Block::new_unsigned(0, 0, String::new(), vec![], keys.public());
+    let block = Block::new_unsigned_at(
+        0,
+        0,
+        String::new(),
+        vec![],
+        keys.public(),
+        GENESIS_TIMESTAMP.to_string(),
+    );
     block.sign(keys).expect("failed to sign block")
 });
 
@@ -16,6 +33,54 @@ pub fn init_blockchain() -> Blockchain {
     Blockchain::from(vec![LazyLock::force(&GENESIS_BLOCK).to_owned()])
 }
 
+pub fn genesis_hash() -> String {
+    let g = GENESIS_BLOCK;
+    LazyLock::force(&g).hash_ref().to_string()
+}
+
+/// Checks that `chain` starts at our genesis block, that every block is valid and links to
+/// the one before it, that its transfers only spend unspent outputs of their senders & that
+/// its escrows follow the claim & refund rules. Returns the outputs left unspent
+pub fn validate_chain(chain: &Blockchain) -> MainResult<Ledger> {
+    let mut blocks = chain.iter_vals();
+    let genesis = blocks.next().ok_or("chain is empty")?;
+    let k = BOOT_NODE_KEYPAIR;
+    let boot_key = LazyLock::force(&k).public();
+    if genesis.hash_ref() != genesis_hash()
+        || !genesis.is_valid()
+        || !genesis.verify_signature(&boot_key)
+    {
+        return Err("chain does not start with our genesis block".into());
+    }
+    let mut ledger = Ledger::default();
+    ledger.apply(genesis)?;
+    let mut previous = genesis;
+    for (index, block) in blocks.enumerate().map(|(i, b)| (i as u64 + 1, b)) {
+        if block.index() != index {
+            return Err(format!("block {} is at index {index}", block.index()).into());
+        }
+        if block.previous_hash() != previous.hash_ref() {
+            return Err(format!("block {index} does not link to the block before it").into());
+        }
+        if !block.is_valid() {
+            return Err(format!("block {index} has an invalid hash").into());
+        }
+        ledger
+            .apply(block)
+            .map_err(|err| format!("block {index} is invalid: {err}"))?;
+        previous = block;
+    }
+    Ok(ledger)
+}
+
+/// Index of the block the transfer with `hash` was included in, if it has been
+pub fn find_transfer(chain: &Blockchain, hash: &str) -> Option<u64> {
+    chain.iter_vals().find_map(|block| {
+        let transfers: &MapVec<String, Transfer> = block.get_ref();
+        transfers.get(&hash.to_string()).map(|_| block.index())
+    })
+}
+
 /// boot node private key in boot.key, which was generated with
 /// ```shell
 /// head -c 32 /dev/urandom > boot.key
@@ -25,9 +90,20 @@ pub const BOOT_NODE_KEY: &[u8; 32] = &[
     212, 60, 214, 85, 84, 109, 59, 48, 212, 32, 49, 55, 254, 81, 225, 32, 100, 66, 43, 210, 78, 57,
     0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
 ];
-pub const BOOT_NODE_PEER_ID: &str = "12D3KooWCwDGQ5jED2DCkdjLpfitvBr6KMDW3VkFLMxE4f67vUen";
+/// Peer id of `BOOT_NODE_KEYPAIR`
+pub const BOOT_NODE_PEER_ID: &str = "12D3KooWEqYXzrvV7KpGrMnVkcPbQJhVbtreWurVigVUZCrLKQPE";
 pub const BOOT_NODE_LOCAL_ADDR: &str = "/ip4/127.0.0.1/udp/62649/quic-v1";
 pub const BOOT_NODE_LISTEN_ADDR: &str = "/ip4/0.0.0.0/udp/62649/quic-v1";
+pub const BOOT_NODE_LOCAL_ADDR_TCP: &str = "/ip4/127.0.0.1/tcp/62649";
+pub const BOOT_NODE_LISTEN_ADDR_TCP: &str = "/ip4/0.0.0.0/tcp/62649";
+pub const BOOT_NODE_LOCAL_ADDR_WS: &str = "/ip4/127.0.0.1/tcp/62650/ws";
+pub const BOOT_NODE_LISTEN_ADDR_WS: &str = "/ip4/0.0.0.0/tcp/62650/ws";
+
+pub fn boot_node_peer_id() -> PeerId {
+    BOOT_NODE_PEER_ID
+        .parse()
+        .expect("failed to parse boot node peer id")
+}
 
 pub const BOOT_NODE_KEYPAIR: LazyLock<Keypair> = LazyLock::new(|| {
     let mut bytes = BOOT_NODE_KEY.to_vec();
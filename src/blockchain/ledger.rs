@@ -0,0 +1,161 @@
+use super::{
+    block::Block,
+    transaction::{
+        escrow::{Escrow, EscrowAction, EscrowLedger},
+        mint::Mint,
+        transfer::Transfer,
+        UTXO,
+    },
+};
+use crate::{
+    util::{
+        hash::Hash,
+        map_vec::{Contains, MapVec},
+        PublicKeyBytes,
+    },
+    MainResult,
+};
+use std::collections::{HashMap, HashSet};
+
+/// Id an output is spent by, `index` is its position among the outputs of `source`.
+/// Transfers' outputs come from the transfer, mint outputs from the block minting them &
+/// funds an escrow releases or gives back from the escrow
+pub fn output_id(source: &str, index: usize) -> String {
+    format!("{source}:{index}")
+}
+
+/// Outputs that haven't been spent & funds locked in escrow, built up block by block as a
+/// chain is validated
+#[derive(Debug, Default, Clone)]
+pub struct Ledger {
+    unspent: HashMap<String, UTXO>,
+    escrows: EscrowLedger,
+}
+
+impl Ledger {
+    /// Spends the block's transfers in order, then applies its escrows in order & adds its
+    /// mint's outputs
+    pub fn apply(&mut self, block: &Block) -> MainResult<()> {
+        let transfers: &MapVec<String, Transfer> = block.get_ref();
+        for transfer in transfers.iter_vals() {
+            self.spend(transfer)
+                .map_err(|err| format!("transfer {} is invalid: {err}", transfer.hash_ref()))?;
+        }
+        for escrow in block.escrows() {
+            self.apply_escrow(block.index(), escrow)
+                .map_err(|err| format!("escrow {} is invalid: {err}", escrow.hash_ref()))?;
+        }
+        let mint: &Mint = block.get_ref();
+        let outputs: &MapVec<PublicKeyBytes, UTXO> = mint.get_ref();
+        self.add_outputs(block.hash_ref(), outputs.iter_vals());
+        Ok(())
+    }
+
+    /// Errors unless the sender signed `transfer` & it spends at least what it pays out of
+    /// outputs the sender owns that haven't been spent
+    pub fn check(&self, transfer: &Transfer) -> MainResult<()> {
+        if !transfer.verify_signature() {
+            return Err("transfer isn't signed by its sender".into());
+        }
+        let spent = self.spendable_total(transfer.sender(), transfer.inputs())?;
+        let outputs: &MapVec<String, UTXO> = transfer.get_ref();
+        let mut paid = 0.;
+        for output in outputs.iter_vals() {
+            if !output.amount().is_finite() || *output.amount() <= 0. {
+                return Err(format!("can't pay out {}", output.amount()).into());
+            }
+            paid += output.amount();
+        }
+        if paid > spent {
+            return Err(format!("pays out {paid} but only spends {spent}").into());
+        }
+        Ok(())
+    }
+
+    /// Checks `transfer`, then replaces its inputs with its outputs
+    pub fn spend(&mut self, transfer: &Transfer) -> MainResult<()> {
+        self.check(transfer)?;
+        for input in transfer.inputs() {
+            self.unspent.remove(input);
+        }
+        let outputs: &MapVec<String, UTXO> = transfer.get_ref();
+        self.add_outputs(transfer.hash_ref(), outputs.iter_vals());
+        Ok(())
+    }
+
+    /// Applies an escrow included in the block at `height`. Locks spend unspent outputs of
+    /// the client that signed them, covering at least the locked amount, & the change goes
+    /// back to the client. The funds claims & refunds release can be spent from then on
+    pub fn apply_escrow(&mut self, height: u64, escrow: &Escrow) -> MainResult<()> {
+        if !escrow.verify_signature() {
+            return Err("escrow isn't signed by its signer".into());
+        }
+        let EscrowAction::Lock { amount, inputs, .. } = escrow.action() else {
+            let released = self.escrows.apply(height, escrow)?;
+            self.add_outputs(escrow.hash_ref(), released.iter());
+            return Ok(());
+        };
+        let spent = self.spendable_total(escrow.signer(), inputs)?;
+        if spent < *amount {
+            return Err(format!("locks {amount} but only spends {spent}").into());
+        }
+        self.escrows.apply(height, escrow)?;
+        for input in inputs {
+            self.unspent.remove(input);
+        }
+        if spent > *amount {
+            let change = UTXO::new(spent - amount, escrow.signer().clone());
+            self.add_outputs(escrow.hash_ref(), [change].iter());
+        }
+        Ok(())
+    }
+
+    /// What `inputs` hold, erroring unless they're distinct unspent outputs `owner` owns
+    fn spendable_total(&self, owner: &PublicKeyBytes, inputs: &[String]) -> MainResult<f64> {
+        let mut spent = 0.;
+        let mut seen = HashSet::new();
+        for input in inputs {
+            if !seen.insert(input) {
+                return Err(format!("output {input} is spent twice").into());
+            }
+            let output = self
+                .unspent
+                .get(input)
+                .ok_or_else(|| format!("output {input} doesn't exist or was spent"))?;
+            let receiver: &PublicKeyBytes = output.get_ref();
+            if receiver != owner {
+                return Err(format!("output {input} isn't the signer's").into());
+            }
+            spent += output.amount();
+        }
+        Ok(spent)
+    }
+
+    fn add_outputs<'u>(&mut self, source: &str, outputs: impl Iterator<Item = &'u UTXO>) {
+        for (index, output) in outputs.enumerate() {
+            self.unspent
+                .insert(output_id(source, index), output.clone());
+        }
+    }
+
+    /// Every unspent output `owner` can spend, by id
+    pub fn spendable<'l>(
+        &'l self,
+        owner: &'l PublicKeyBytes,
+    ) -> impl Iterator<Item = (&'l String, &'l UTXO)> {
+        self.unspent.iter().filter(move |(_, output)| {
+            let receiver: &PublicKeyBytes = output.get_ref();
+            receiver == owner
+        })
+    }
+
+    pub fn escrows(&self) -> &EscrowLedger {
+        &self.escrows
+    }
+
+    pub fn balance(&self, owner: &PublicKeyBytes) -> f64 {
+        self.spendable(owner)
+            .map(|(_, output)| output.amount())
+            .sum()
+    }
+}
@@ -1,5 +1,6 @@
 pub mod block;
 pub mod chain;
+pub mod ledger;
 pub mod transaction;
 use libp2p::identity::SigningError;
 
@@ -13,4 +14,6 @@ pub enum ChainError {
     HashInvalid,
     #[error("Tried to sign transaction which has already been signed")]
     SignatureExists,
+    #[error("Only its sender can sign a transaction")]
+    NotSender,
 }
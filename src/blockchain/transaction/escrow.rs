@@ -0,0 +1,290 @@
+use crate::{
+    behaviour::{receipt::SignedReceipt, AuctionId},
+    blockchain::{ChainError, ChainResult},
+    util::{hash::Hash, map_vec::Contains, now_timestamp_string, PublicKeyBytes},
+    MainResult,
+};
+use libp2p::{
+    identity::{Keypair, PublicKey},
+    PeerId,
+};
+use serde::{Deserialize, Serialize};
+use sha3::Digest;
+use std::collections::HashMap;
+
+use super::UTXO;
+
+/// What an escrow transaction does with a client's funds
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum EscrowAction {
+    /// Locks `amount` of the client's funds for the provider of its session in `auction`
+    Lock {
+        provider: PublicKeyBytes,
+        auction: AuctionId,
+        amount: f64,
+        /// Ids of the client's unspent outputs the funds are taken from, whatever they hold
+        /// beyond `amount` goes back to the client
+        inputs: Vec<String>,
+        /// Height from which the client may take back funds that weren't claimed
+        timeout: u64,
+    },
+    /// Pays the provider what a receipt the client countersigned metered, out of the lock with
+    /// hash `lock`. Whatever is left goes back to the client
+    Claim {
+        lock: String,
+        receipt: Box<SignedReceipt>,
+    },
+    /// Returns the funds of the lock with hash `lock` to the client once it has timed out
+    Refund { lock: String },
+}
+
+/// Locks client funds for a single provider & auction until they are claimed or refunded
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Escrow {
+    pub(super) hash: String,
+    pub(super) timestamp: String,
+    /// The client for locks & refunds, the provider for claims
+    pub(super) signer: PublicKeyBytes,
+    pub(super) action: EscrowAction,
+    pub(super) signature: Option<Vec<u8>>,
+}
+
+impl Contains<String> for Escrow {
+    fn get_ref(&self) -> &String {
+        &self.hash
+    }
+}
+
+pub struct Fields<'h> {
+    timestamp: &'h str,
+    signer: &'h PublicKeyBytes,
+    action: &'h EscrowAction,
+}
+
+impl<'h> From<&'h Escrow> for Fields<'h> {
+    fn from(value: &'h Escrow) -> Self {
+        Self {
+            timestamp: &value.timestamp,
+            signer: &value.signer,
+            action: &value.action,
+        }
+    }
+}
+
+impl<'h> Hash<'h> for Escrow {
+    type Fields = Fields<'h>;
+    fn hash_ref(&self) -> &str {
+        &self.hash
+    }
+    fn hash_fields(fields: Self::Fields) -> sha3::digest::Output<crate::util::hash::Hasher> {
+        let mut hasher = Self::hasher();
+        hasher.update(fields.timestamp);
+        hasher.update(fields.signer.as_ref());
+        hasher.update(serde_json::to_vec(fields.action).expect("failed to serialize escrow"));
+        hasher.finalize()
+    }
+}
+
+impl Escrow {
+    /// Locks `amount` of `client`'s funds, spent out of `inputs`, for `provider` until height
+    /// `timeout`
+    pub fn lock(
+        client: impl Into<PublicKeyBytes>,
+        provider: impl Into<PublicKeyBytes>,
+        auction: AuctionId,
+        amount: f64,
+        inputs: Vec<String>,
+        timeout: u64,
+    ) -> Self {
+        let action = EscrowAction::Lock {
+            provider: provider.into(),
+            auction,
+            amount,
+            inputs,
+            timeout,
+        };
+        Self::new(client.into(), action)
+    }
+
+    pub fn claim(provider: impl Into<PublicKeyBytes>, lock: &str, receipt: SignedReceipt) -> Self {
+        let action = EscrowAction::Claim {
+            lock: lock.to_string(),
+            receipt: Box::new(receipt),
+        };
+        Self::new(provider.into(), action)
+    }
+
+    pub fn refund(client: impl Into<PublicKeyBytes>, lock: &str) -> Self {
+        let action = EscrowAction::Refund {
+            lock: lock.to_string(),
+        };
+        Self::new(client.into(), action)
+    }
+
+    fn new(signer: PublicKeyBytes, action: EscrowAction) -> Self {
+        let timestamp = now_timestamp_string();
+        let fields = Fields {
+            timestamp: &timestamp,
+            signer: &signer,
+            action: &action,
+        };
+        let hash = Self::output_to_string(Self::hash_fields(fields));
+        Self {
+            hash,
+            timestamp,
+            signer,
+            action,
+            signature: None,
+        }
+    }
+
+    pub fn action(&self) -> &EscrowAction {
+        &self.action
+    }
+
+    pub fn signer(&self) -> &PublicKeyBytes {
+        &self.signer
+    }
+
+    /// Signs the escrow's hash, only its signer can
+    pub fn sign(&mut self, keys: &Keypair) -> ChainResult<()> {
+        if self.signature.is_some() {
+            return Err(ChainError::SignatureExists);
+        }
+        if PublicKeyBytes::from(keys.public()) != self.signer {
+            return Err(ChainError::NotSender);
+        }
+        self.signature = Some(keys.sign(self.hash.as_bytes())?);
+        Ok(())
+    }
+
+    /// Whether the signer signed the escrow & it hasn't changed since
+    pub fn verify_signature(&self) -> bool {
+        let Ok(signer): Result<PublicKey, _> = (&self.signer).try_into() else {
+            return false;
+        };
+        self.signature
+            .as_ref()
+            .is_some_and(|signature| signer.verify(self.hash.as_bytes(), signature))
+            && self.valid()
+    }
+}
+
+/// Funds locked by an escrow that hasn't been claimed or refunded yet
+#[derive(Debug, Clone)]
+struct OpenLock {
+    client: PublicKeyBytes,
+    provider: PublicKeyBytes,
+    auction: AuctionId,
+    amount: f64,
+    timeout: u64,
+}
+
+fn peer_id(key: &PublicKeyBytes) -> MainResult<PeerId> {
+    let key: PublicKey = key.try_into()?;
+    Ok(key.to_peer_id())
+}
+
+/// Every lock that is still open, built up block by block as a chain is validated. Whether a
+/// lock's inputs can be spent is up to the `Ledger` holding it
+#[derive(Debug, Default, Clone)]
+pub struct EscrowLedger {
+    open: HashMap<String, OpenLock>,
+}
+
+impl EscrowLedger {
+    /// Applies an escrow included in the block at `height`, returning the funds it releases.
+    /// Locks must time out after `height`, claims must come from the lock's provider before it
+    /// times out with a receipt for its auction that its client countersigned & that doesn't
+    /// cost less than nothing, & refunds from
+    /// the lock's client once it has timed out. Every lock is released once
+    pub fn apply(&mut self, height: u64, escrow: &Escrow) -> MainResult<Vec<UTXO>> {
+        match &escrow.action {
+            EscrowAction::Lock {
+                provider,
+                auction,
+                amount,
+                timeout,
+                ..
+            } => {
+                if !amount.is_finite() || *amount <= 0. {
+                    return Err(format!("can't lock {amount}").into());
+                }
+                if *timeout <= height {
+                    return Err(format!("lock at height {height} times out at {timeout}").into());
+                }
+                if self.open.contains_key(&escrow.hash) {
+                    return Err(format!("lock {} already exists", escrow.hash).into());
+                }
+                let lock = OpenLock {
+                    client: escrow.signer.clone(),
+                    provider: provider.clone(),
+                    auction: *auction,
+                    amount: *amount,
+                    timeout: *timeout,
+                };
+                self.open.insert(escrow.hash.clone(), lock);
+                Ok(vec![])
+            }
+            EscrowAction::Claim { lock, receipt } => {
+                let open = self.open_lock(lock)?;
+                if escrow.signer != open.provider {
+                    return Err(format!("lock {lock} can only be claimed by its provider").into());
+                }
+                if height >= open.timeout {
+                    return Err(format!("lock {lock} timed out at {}", open.timeout).into());
+                }
+                receipt.verify()?;
+                if !receipt.countersigned() {
+                    return Err("claims need a receipt the client countersigned".into());
+                }
+                let metered = &receipt.receipt;
+                if metered.auction != open.auction
+                    || metered.client != peer_id(&open.client)?
+                    || metered.provider != peer_id(&open.provider)?
+                {
+                    return Err(format!("receipt isn't for the session lock {lock} is for").into());
+                }
+                let cost = metered.cost();
+                if !cost.is_finite() || cost < 0. {
+                    return Err(format!("can't pay out a receipt costing {cost}").into());
+                }
+                let open = self.open.remove(lock).unwrap();
+                let paid = cost.min(open.amount);
+                let mut released = vec![];
+                if paid > 0. {
+                    released.push(UTXO::new(paid, open.provider));
+                }
+                if open.amount > paid {
+                    released.push(UTXO::new(open.amount - paid, open.client));
+                }
+                Ok(released)
+            }
+            EscrowAction::Refund { lock } => {
+                let open = self.open_lock(lock)?;
+                if escrow.signer != open.client {
+                    return Err(format!("lock {lock} can only be refunded to its client").into());
+                }
+                if height < open.timeout {
+                    return Err(
+                        format!("lock {lock} doesn't time out until {}", open.timeout).into(),
+                    );
+                }
+                let open = self.open.remove(lock).unwrap();
+                Ok(vec![UTXO::new(open.amount, open.client)])
+            }
+        }
+    }
+
+    /// Whether the lock with hash `lock` still holds funds
+    pub fn is_open(&self, lock: &str) -> bool {
+        self.open.contains_key(lock)
+    }
+
+    fn open_lock(&self, lock: &str) -> MainResult<&OpenLock> {
+        self.open
+            .get(lock)
+            .ok_or_else(|| format!("lock {lock} doesn't exist or was released").into())
+    }
+}
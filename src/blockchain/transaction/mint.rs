@@ -54,6 +54,15 @@ const MINT_INCENTIVE_TOTAL: f64 = 9999.0;
 const PROVIDERS_POOL_PORTION: f64 = 0.15;
 impl Mint {
     pub fn new(transfers: impl AsRef<[Transfer]>, miner_key: PublicKey) -> Self {
+        Self::new_at(transfers, miner_key, now_timestamp_string())
+    }
+
+    /// Same as `new`, but timestamped with `timestamp` rather than now
+    pub fn new_at(
+        transfers: impl AsRef<[Transfer]>,
+        miner_key: PublicKey,
+        timestamp: String,
+    ) -> Self {
         // do some work to get all providers and percents from transfers
         let all_providers_and_percents: Vec<(&PublicKeyBytes, f64)> = vec![];
         let percent_sum = all_providers_and_percents
@@ -76,8 +85,6 @@ impl Mint {
                 outputs.push(UTXO::new(amt, id.clone()));
             });
 
-        let timestamp = now_timestamp_string();
-
         let fields = Fields {
             timestamp: &timestamp,
             outputs: &outputs,
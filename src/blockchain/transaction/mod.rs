@@ -1,6 +1,7 @@
 use crate::util::{hash::Hash, map_vec::Contains, PublicKeyBytes};
 use sha3::Digest;
 
+pub mod escrow;
 pub mod mint;
 pub mod transfer;
 
@@ -1,7 +1,12 @@
-use crate::util::{
-    map_vec::{Contains, MapVec},
-    PublicKeyBytes,
+use crate::{
+    blockchain::{ChainError, ChainResult},
+    util::{
+        hash::Hash,
+        map_vec::{Contains, MapVec},
+        now_timestamp_string, PublicKeyBytes,
+    },
 };
+use libp2p::identity::{Keypair, PublicKey};
 use serde::{Deserialize, Serialize};
 use sha3::Digest;
 
@@ -14,6 +19,9 @@ pub struct Transfer {
     pub(super) tokens: f64,
     pub(super) inputs: Vec<String>,
     pub(super) outputs: MapVec<String, super::UTXO>,
+    /// Digest of the usage receipt the transfer pays for, if it settles a session
+    #[serde(default)]
+    pub(super) receipt: Option<String>,
     pub(super) signature: Option<Vec<u8>>,
 }
 
@@ -36,6 +44,7 @@ pub struct Fields<'h> {
     tokens: &'h f64,
     inputs: &'h [String],
     outputs: &'h [super::UTXO],
+    receipt: Option<&'h str>,
 }
 
 impl<'h> From<&'h Transfer> for Fields<'h> {
@@ -47,11 +56,12 @@ impl<'h> From<&'h Transfer> for Fields<'h> {
             tokens: &value.tokens,
             inputs: &value.inputs,
             outputs: &value.outputs.as_ref(),
+            receipt: value.receipt.as_deref(),
         }
     }
 }
 
-impl<'h> crate::util::hash::Hash<'h> for Transfer {
+impl<'h> Hash<'h> for Transfer {
     type Fields = Fields<'h>;
     fn hash_ref(&self) -> &str {
         &self.hash
@@ -64,6 +74,95 @@ impl<'h> crate::util::hash::Hash<'h> for Transfer {
         hasher.update(fields.tokens.to_string());
         Self::update_multiple(&mut hasher, fields.inputs);
         Self::update_multiple(&mut hasher, fields.outputs);
+        if let Some(receipt) = fields.receipt {
+            hasher.update(receipt);
+        }
         hasher.finalize()
     }
 }
+
+impl Transfer {
+    /// Pays `tokens` to `receiver` out of `inputs`, unsigned until its sender `sign`s it
+    pub fn new(
+        sender: impl Into<PublicKeyBytes>,
+        receiver: impl Into<PublicKeyBytes>,
+        tokens: f64,
+        inputs: Vec<String>,
+        receipt: Option<String>,
+    ) -> Self {
+        let (sender, receiver) = (sender.into(), receiver.into());
+        let outputs = MapVec::from(vec![super::UTXO::new(tokens, receiver.clone())]);
+        let timestamp = now_timestamp_string();
+        let fields = Fields {
+            timestamp: &timestamp,
+            sender: &sender,
+            receiver: &receiver,
+            tokens: &tokens,
+            inputs: &inputs,
+            outputs: outputs.as_ref(),
+            receipt: receipt.as_deref(),
+        };
+        let hash = Self::output_to_string(Self::hash_fields(fields));
+        Self {
+            hash,
+            timestamp,
+            sender,
+            receiver,
+            tokens,
+            inputs,
+            outputs,
+            receipt,
+            signature: None,
+        }
+    }
+
+    /// Returns whatever `inputs` hold beyond `tokens` to the sender, before it is signed
+    pub fn with_change(mut self, change: f64) -> Self {
+        if change > 0. {
+            self.outputs
+                .push(super::UTXO::new(change, self.sender.clone()));
+            self.hash = Self::output_to_string(self.my_hash());
+        }
+        self
+    }
+
+    pub fn tokens(&self) -> f64 {
+        self.tokens
+    }
+
+    pub fn sender(&self) -> &PublicKeyBytes {
+        &self.sender
+    }
+
+    /// Ids of the outputs the transfer spends, see `ledger::output_id`
+    pub fn inputs(&self) -> &[String] {
+        &self.inputs
+    }
+
+    pub fn receipt(&self) -> Option<&str> {
+        self.receipt.as_deref()
+    }
+
+    /// Signs the transfer's hash, only its sender can
+    pub fn sign(&mut self, keys: &Keypair) -> ChainResult<()> {
+        if self.signature.is_some() {
+            return Err(ChainError::SignatureExists);
+        }
+        if PublicKeyBytes::from(keys.public()) != self.sender {
+            return Err(ChainError::NotSender);
+        }
+        self.signature = Some(keys.sign(self.hash.as_bytes())?);
+        Ok(())
+    }
+
+    /// Whether the sender signed the transfer & it hasn't changed since
+    pub fn verify_signature(&self) -> bool {
+        let Ok(sender): Result<PublicKey, _> = (&self.sender).try_into() else {
+            return false;
+        };
+        self.signature
+            .as_ref()
+            .is_some_and(|signature| sender.verify(self.hash.as_bytes(), signature))
+            && self.valid()
+    }
+}
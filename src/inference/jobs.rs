@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Sessions a provider is serving or has queued, shared with the tasks serving them.
+/// At most `capacity` sessions run at once & at most `queue_size` more wait for a slot
+#[derive(Debug, Clone)]
+pub struct Jobs {
+    slots: Arc<Semaphore>,
+    counts: Arc<Mutex<Load>>,
+}
+
+/// How busy a provider is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Load {
+    pub active: usize,
+    pub queued: usize,
+    pub capacity: usize,
+    pub queue_size: usize,
+}
+
+impl Load {
+    /// Share of the session slots in use, above 1 once jobs are queued
+    pub fn utilization(&self) -> f32 {
+        (self.active + self.queued) as f32 / self.capacity.max(1) as f32
+    }
+
+    /// Whether another job can be queued
+    pub fn has_room(&self) -> bool {
+        self.active + self.queued < self.capacity + self.queue_size
+    }
+}
+
+impl Jobs {
+    pub fn new(capacity: usize, queue_size: usize) -> Self {
+        Self {
+            slots: Arc::new(Semaphore::new(capacity)),
+            counts: Arc::new(Mutex::new(Load {
+                capacity,
+                queue_size,
+                ..Default::default()
+            })),
+        }
+    }
+
+    pub fn load(&self) -> Load {
+        *self.counts.lock().unwrap()
+    }
+
+    /// Queues a job, None if the queue is full.
+    /// Its place is given up when the returned job is dropped
+    pub fn enqueue(&self) -> Option<QueuedJob> {
+        let mut counts = self.counts.lock().unwrap();
+        if !counts.has_room() {
+            return None;
+        }
+        counts.queued += 1;
+        Some(QueuedJob {
+            jobs: self.clone(),
+            started: false,
+        })
+    }
+}
+
+/// A job waiting for a session slot
+#[derive(Debug)]
+pub struct QueuedJob {
+    jobs: Jobs,
+    started: bool,
+}
+
+impl QueuedJob {
+    /// Waits for a session slot, which is freed once the returned job is dropped
+    pub async fn start(mut self) -> ActiveJob {
+        let permit = Arc::clone(&self.jobs.slots)
+            .acquire_owned()
+            .await
+            .expect("session slots are never closed");
+        let mut counts = self.jobs.counts.lock().unwrap();
+        counts.queued -= 1;
+        counts.active += 1;
+        self.started = true;
+        ActiveJob {
+            jobs: self.jobs.clone(),
+            _permit: permit,
+        }
+    }
+}
+
+impl Drop for QueuedJob {
+    fn drop(&mut self) {
+        if !self.started {
+            self.jobs.counts.lock().unwrap().queued -= 1;
+        }
+    }
+}
+
+/// A job holding one of the session slots
+#[derive(Debug)]
+pub struct ActiveJob {
+    jobs: Jobs,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl Drop for ActiveJob {
+    fn drop(&mut self) {
+        self.jobs.counts.lock().unwrap().active -= 1;
+    }
+}
@@ -0,0 +1,51 @@
+use super::{InferenceBackend, TokenStream};
+use crate::{behaviour::streaming::InferenceParams, MainResult};
+use futures::{stream, StreamExt};
+
+/// Deterministic backend for tests & local networks without a model.
+/// Completes every prompt with itself unless a reply is set, one word per token.
+/// Resumed completions skip the output already given
+#[derive(Debug, Default, Clone)]
+pub struct MockBackend {
+    reply: Option<String>,
+}
+
+impl MockBackend {
+    /// Completes every prompt with `reply`
+    pub fn replying(reply: impl Into<String>) -> Self {
+        Self {
+            reply: Some(reply.into()),
+        }
+    }
+
+    fn tokens(text: &str) -> TokenStream {
+        let tokens: Vec<MainResult<String>> = text
+            .split_inclusive(' ')
+            .map(|token| Ok(token.to_string()))
+            .collect();
+        stream::iter(tokens).boxed()
+    }
+}
+
+impl InferenceBackend for MockBackend {
+    fn complete(
+        &self,
+        _model: &str,
+        prompt: &str,
+        _params: &InferenceParams,
+    ) -> MainResult<TokenStream> {
+        let text = self.reply.as_deref().unwrap_or(prompt);
+        Ok(Self::tokens(text))
+    }
+
+    fn resume(
+        &self,
+        _model: &str,
+        prompt: &str,
+        output: &str,
+        _params: &InferenceParams,
+    ) -> MainResult<TokenStream> {
+        let text = self.reply.as_deref().unwrap_or(prompt);
+        Ok(Self::tokens(text.strip_prefix(output).unwrap_or(text)))
+    }
+}
@@ -0,0 +1,34 @@
+pub mod jobs;
+pub mod mock;
+pub mod subprocess;
+pub mod throughput;
+
+use crate::{behaviour::streaming::InferenceParams, MainResult};
+use futures::stream::BoxStream;
+
+/// Tokens of a completion in the order they are produced, nothing follows an error
+pub type TokenStream = BoxStream<'static, MainResult<String>>;
+
+/// Produces the completions a provider serves over inference sessions
+pub trait InferenceBackend: std::fmt::Debug + Send + Sync {
+    /// Starts completing `prompt` with `model`.
+    /// The session stops polling the stream once `params.max_tokens` have been sent
+    fn complete(
+        &self,
+        model: &str,
+        prompt: &str,
+        params: &InferenceParams,
+    ) -> MainResult<TokenStream>;
+
+    /// Continues a completion of `prompt` another provider got as far as `output` with.
+    /// Completes the prompt followed by the output unless the backend knows better
+    fn resume(
+        &self,
+        model: &str,
+        prompt: &str,
+        output: &str,
+        params: &InferenceParams,
+    ) -> MainResult<TokenStream> {
+        self.complete(model, &format!("{prompt}{output}"), params)
+    }
+}
@@ -0,0 +1,141 @@
+use super::{InferenceBackend, TokenStream};
+use crate::{behaviour::streaming::InferenceParams, MainResult};
+use futures::{stream, StreamExt};
+use std::process::Stdio;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    process::{Child, ChildStdout, Command},
+};
+
+/// Environment variable holding the requested model
+pub const MODEL_VAR: &str = "INFERENCE_MODEL";
+/// Environment variable holding the most tokens the session will send
+pub const MAX_TOKENS_VAR: &str = "INFERENCE_MAX_TOKENS";
+/// Environment variable holding the requested sampling temperature
+pub const TEMPERATURE_VAR: &str = "INFERENCE_TEMPERATURE";
+/// Bytes read from the command's stdout at a time
+const READ_SIZE: usize = 1024;
+
+/// Runs a local command for every session.
+/// The prompt is written to its stdin & its stdout is streamed back a word at a time, the model
+/// and parameters are passed in `MODEL_VAR`, `MAX_TOKENS_VAR` & `TEMPERATURE_VAR`
+#[derive(Debug, Clone)]
+pub struct SubprocessBackend {
+    program: String,
+    args: Vec<String>,
+}
+
+impl SubprocessBackend {
+    pub fn new(program: impl Into<String>, args: Vec<String>) -> Self {
+        Self {
+            program: program.into(),
+            args,
+        }
+    }
+
+    /// Runs `command` with `sh -c`
+    pub fn shell(command: impl Into<String>) -> Self {
+        Self::new("sh", vec!["-c".to_string(), command.into()])
+    }
+}
+
+impl InferenceBackend for SubprocessBackend {
+    fn complete(
+        &self,
+        model: &str,
+        prompt: &str,
+        params: &InferenceParams,
+    ) -> MainResult<TokenStream> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .env(MODEL_VAR, model)
+            .env(MAX_TOKENS_VAR, params.max_tokens.to_string())
+            .env(TEMPERATURE_VAR, params.temperature.to_string())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()?;
+        let mut stdin = child.stdin.take().ok_or("backend has no stdin")?;
+        let stdout = child.stdout.take().ok_or("backend has no stdout")?;
+
+        // written separately so commands that stream before reading all their input can't deadlock
+        let prompt = prompt.to_string();
+        tokio::spawn(async move {
+            if let Err(err) = stdin.write_all(prompt.as_bytes()).await {
+                tracing::debug!("backend stopped reading the prompt: {err}");
+            }
+        });
+
+        let output = Output {
+            child,
+            stdout,
+            pending: vec![],
+            eof: false,
+            exited: false,
+        };
+        Ok(stream::unfold(output, |mut output| async move {
+            let token = output.next_token().await?;
+            Some((token, output))
+        })
+        .boxed())
+    }
+}
+
+/// What has been read from a running command but not yet streamed
+struct Output {
+    child: Child,
+    stdout: ChildStdout,
+    pending: Vec<u8>,
+    eof: bool,
+    exited: bool,
+}
+
+impl Output {
+    /// None once stdout is exhausted & the command exited successfully
+    async fn next_token(&mut self) -> Option<MainResult<String>> {
+        loop {
+            if let Some(token) = self.split_token() {
+                return Some(Ok(token));
+            }
+            if self.eof {
+                if !self.pending.is_empty() {
+                    let rest = std::mem::take(&mut self.pending);
+                    return Some(Ok(String::from_utf8_lossy(&rest).into_owned()));
+                }
+                if self.exited {
+                    return None;
+                }
+                self.exited = true;
+                return match self.child.wait().await {
+                    Ok(status) if status.success() => None,
+                    Ok(status) => Some(Err(format!("backend {status}").into())),
+                    Err(err) => Some(Err(err.into())),
+                };
+            }
+
+            let mut buf = [0u8; READ_SIZE];
+            match self.stdout.read(&mut buf).await {
+                Ok(0) => self.eof = true,
+                Ok(n) => self.pending.extend_from_slice(&buf[..n]),
+                Err(err) => {
+                    self.eof = true;
+                    self.exited = true;
+                    return Some(Err(err.into()));
+                }
+            }
+        }
+    }
+
+    /// Takes the first word & the whitespace ending it, splitting on ascii whitespace keeps
+    /// multi byte characters whole
+    fn split_token(&mut self) -> Option<String> {
+        let start = self.pending.iter().position(|b| !b.is_ascii_whitespace())?;
+        let end = start
+            + self.pending[start..]
+                .iter()
+                .position(u8::is_ascii_whitespace)?;
+        let token: Vec<u8> = self.pending.drain(..=end).collect();
+        Some(String::from_utf8_lossy(&token).into_owned())
+    }
+}
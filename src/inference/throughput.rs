@@ -0,0 +1,103 @@
+use super::{InferenceBackend, TokenStream};
+use crate::{behaviour::streaming::InferenceParams, MainResult};
+use futures::StreamExt;
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Weight of the latest completion in the measured throughput
+const SMOOTHING: f32 = 0.3;
+
+/// Tokens per second a backend has been measured generating, shared with the tasks serving
+/// sessions. A moving average over completions, recent ones count most
+#[derive(Debug, Clone, Default)]
+pub struct Throughput(Arc<Mutex<Option<f32>>>);
+
+impl Throughput {
+    /// Records a completion that produced `tokens` over `elapsed`, empty ones are ignored
+    pub fn record(&self, tokens: u64, elapsed: Duration) {
+        if tokens == 0 || elapsed.is_zero() {
+            return;
+        }
+        let latest = tokens as f32 / elapsed.as_secs_f32();
+        let mut measured = self.0.lock().unwrap();
+        *measured = Some(match *measured {
+            Some(average) => average + SMOOTHING * (latest - average),
+            None => latest,
+        });
+    }
+
+    /// None until a completion has been recorded
+    pub fn tokens_per_sec(&self) -> Option<f32> {
+        *self.0.lock().unwrap()
+    }
+}
+
+/// Times every completion of the wrapped backend, from the request until the stream is
+/// finished or dropped
+#[derive(Debug)]
+pub struct MeteredBackend {
+    backend: Arc<dyn InferenceBackend>,
+    throughput: Throughput,
+}
+
+impl MeteredBackend {
+    pub fn new(backend: Arc<dyn InferenceBackend>, throughput: Throughput) -> Self {
+        Self {
+            backend,
+            throughput,
+        }
+    }
+
+    fn meter(&self, tokens: TokenStream) -> TokenStream {
+        let mut meter = Meter {
+            throughput: self.throughput.clone(),
+            start: Instant::now(),
+            tokens: 0,
+        };
+        tokens.inspect(move |token| meter.count(token)).boxed()
+    }
+}
+
+impl InferenceBackend for MeteredBackend {
+    fn complete(
+        &self,
+        model: &str,
+        prompt: &str,
+        params: &InferenceParams,
+    ) -> MainResult<TokenStream> {
+        Ok(self.meter(self.backend.complete(model, prompt, params)?))
+    }
+
+    fn resume(
+        &self,
+        model: &str,
+        prompt: &str,
+        output: &str,
+        params: &InferenceParams,
+    ) -> MainResult<TokenStream> {
+        Ok(self.meter(self.backend.resume(model, prompt, output, params)?))
+    }
+}
+
+/// Counts the tokens of one completion, recorded once its stream is dropped
+struct Meter {
+    throughput: Throughput,
+    start: Instant,
+    tokens: u64,
+}
+
+impl Meter {
+    fn count(&mut self, token: &MainResult<String>) {
+        if token.is_ok() {
+            self.tokens += 1;
+        }
+    }
+}
+
+impl Drop for Meter {
+    fn drop(&mut self) {
+        self.throughput.record(self.tokens, self.start.elapsed());
+    }
+}
@@ -0,0 +1,151 @@
+use crate::{behaviour::status::NodeRole, MainResult};
+use libp2p::{Multiaddr, PeerId};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// File the address book is kept in, within the data directory
+const ADDRESS_BOOK_FILE: &str = "peers.json";
+/// Most addresses remembered for a single peer
+const MAX_ADDRESSES: usize = 8;
+
+/// Everything remembered about a peer we have connected to
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct PeerRecord {
+    pub addresses: Vec<Multiaddr>,
+    pub role: Option<NodeRole>,
+    /// Seconds since the unix epoch we were last connected
+    pub last_seen: u64,
+    pub successes: u32,
+    pub failures: u32,
+}
+
+impl PeerRecord {
+    /// Share of dials that succeeded, peers we know nothing about sit at 0.5
+    pub fn reliability(&self) -> f64 {
+        (self.successes as f64 + 1.) / ((self.successes + self.failures) as f64 + 2.)
+    }
+}
+
+/// Peers we have connected to, persisted in the data directory so they can be reconnected to
+/// on startup
+#[derive(Debug, Default)]
+pub struct AddressBook {
+    /// None when running without a data directory, nothing is persisted
+    path: Option<PathBuf>,
+    peers: HashMap<PeerId, PeerRecord>,
+    dirty: bool,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+impl AddressBook {
+    /// Loads the address book from `data_dir`, starting empty if there is none yet
+    pub fn load(data_dir: Option<&Path>) -> MainResult<Self> {
+        let path = data_dir.map(|dir| dir.join(ADDRESS_BOOK_FILE));
+        let peers = match path.as_ref().filter(|path| path.exists()) {
+            Some(path) => serde_json::from_slice(&std::fs::read(path)?)?,
+            None => HashMap::new(),
+        };
+        Ok(Self {
+            path,
+            peers,
+            dirty: false,
+        })
+    }
+
+    /// Writes the address book to the data directory if it changed since it was last saved
+    pub fn save(&mut self) -> MainResult<()> {
+        let Some(path) = self.path.as_ref().filter(|_| self.dirty) else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_vec_pretty(&self.peers)?)?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    pub fn get(&self, peer: &PeerId) -> Option<&PeerRecord> {
+        self.peers.get(peer)
+    }
+
+    pub fn len(&self) -> usize {
+        self.peers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.peers.is_empty()
+    }
+
+    /// Records a connection to `peer`, along with the address it was dialed on if we dialed it
+    pub fn record_connected(&mut self, peer: PeerId, dialed: Option<Multiaddr>) {
+        let record = self.peers.entry(peer).or_default();
+        record.successes += 1;
+        record.last_seen = now_secs();
+        if let Some(address) = dialed {
+            Self::add_address(record, address);
+        }
+        self.dirty = true;
+    }
+
+    /// Records a failed dial, only for peers already in the book
+    pub fn record_failure(&mut self, peer: &PeerId) {
+        if let Some(record) = self.peers.get_mut(peer) {
+            record.failures += 1;
+            self.dirty = true;
+        }
+    }
+
+    pub fn record_role(&mut self, peer: PeerId, role: NodeRole) {
+        self.peers.entry(peer).or_default().role = Some(role);
+        self.dirty = true;
+    }
+
+    /// Remembers the addresses a connected peer says it listens on
+    pub fn record_addresses(&mut self, peer: &PeerId, addresses: Vec<Multiaddr>) {
+        if let Some(record) = self.peers.get_mut(peer) {
+            for address in addresses {
+                Self::add_address(record, address);
+            }
+            self.dirty = true;
+        }
+    }
+
+    /// Forgets a peer entirely, ie. because it is on another network
+    pub fn remove(&mut self, peer: &PeerId) {
+        if self.peers.remove(peer).is_some() {
+            self.dirty = true;
+        }
+    }
+
+    /// Most reliable peers first, ties are broken by which was seen most recently
+    pub fn best(&self) -> Vec<(&PeerId, &PeerRecord)> {
+        let mut peers: Vec<_> = self.peers.iter().collect();
+        peers.sort_by(|(_, a), (_, b)| {
+            b.reliability()
+                .total_cmp(&a.reliability())
+                .then(b.last_seen.cmp(&a.last_seen))
+        });
+        peers
+    }
+
+    fn add_address(record: &mut PeerRecord, address: Multiaddr) {
+        if record.addresses.contains(&address) {
+            return;
+        }
+        if record.addresses.len() >= MAX_ADDRESSES {
+            record.addresses.remove(0);
+        }
+        record.addresses.push(address);
+    }
+}
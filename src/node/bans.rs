@@ -0,0 +1,49 @@
+use libp2p::PeerId;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// Peers banned at runtime for misbehaving, along with when each ban is lifted
+#[derive(Debug, Default)]
+pub struct BanList {
+    bans: HashMap<PeerId, Instant>,
+}
+
+impl BanList {
+    /// Bans `peer` for `duration`, extending any existing ban.
+    /// Returns false if the peer was already banned
+    pub fn ban(&mut self, peer: PeerId, duration: Duration) -> bool {
+        let until = Instant::now() + duration;
+        match self.bans.insert(peer, until) {
+            Some(previous) => {
+                self.bans.insert(peer, previous.max(until));
+                false
+            }
+            None => true,
+        }
+    }
+
+    pub fn is_banned(&self, peer: &PeerId) -> bool {
+        self.bans.contains_key(peer)
+    }
+
+    /// Removes & returns every peer whose ban has expired by `now`
+    pub fn expire(&mut self, now: Instant) -> Vec<PeerId> {
+        let expired: Vec<PeerId> = self
+            .bans
+            .iter()
+            .filter(|(_, until)| now >= **until)
+            .map(|(peer, _)| *peer)
+            .collect();
+        for peer in expired.iter() {
+            self.bans.remove(peer);
+        }
+        expired
+    }
+
+    /// Every banned peer & when its ban is lifted
+    pub fn iter(&self) -> impl Iterator<Item = (&PeerId, &Instant)> {
+        self.bans.iter()
+    }
+}
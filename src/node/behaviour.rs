@@ -1,15 +1,20 @@
 use libp2p::{
+    allow_block_list::{self, AllowedPeers, BlockedPeers},
+    connection_limits,
     gossipsub::{self, MessageAuthenticity},
     identify,
     identity::Keypair,
-    rendezvous,
+    kad::{self, store::MemoryStore},
+    mdns, rendezvous,
     request_response::{self, ProtocolSupport},
-    swarm::NetworkBehaviour,
+    swarm::{behaviour::toggle::Toggle, NetworkBehaviour},
     StreamProtocol,
 };
 use std::hash::{DefaultHasher, Hash, Hasher};
 
+use super::config::NodeConfig;
 use crate::behaviour::{
+    dht::{Kademlia, KAD_PROTOCOL, KAD_QUERY_TIMEOUT},
     req_res::{NetworkReqRes, NetworkRequest, NetworkResponse},
     IDENTIFY_ID,
 };
@@ -18,8 +23,16 @@ use crate::behaviour::{
 /// Should never be manually instantiated
 #[derive(NetworkBehaviour)]
 pub struct SharedBehaviour {
+    pub limits: connection_limits::Behaviour,
+    /// Only enabled when `NodeConfig::allowed_peers` is not empty
+    pub allowed: Toggle<allow_block_list::Behaviour<AllowedPeers>>,
+    /// Statically denied & banned peers
+    pub blocked: allow_block_list::Behaviour<BlockedPeers>,
     pub gossip: gossipsub::Behaviour,
     pub identify: identify::Behaviour,
+    pub kad: Kademlia,
+    /// Only enabled when `NodeConfig::mdns` is set
+    pub mdns: Toggle<mdns::tokio::Behaviour>,
     pub req_res: NetworkReqRes,
     pub stream: libp2p_stream::Behaviour,
 }
@@ -28,6 +41,8 @@ pub struct SharedBehaviour {
 pub enum NodeBehaviourEvent {
     Identify(identify::Event),
     Gossip(gossipsub::Event),
+    Kad(kad::Event),
+    Mdns(mdns::Event),
     ReqRes(request_response::Event<NetworkRequest, NetworkResponse>),
     RendezvousServer(rendezvous::server::Event),
     RendezvousClient(rendezvous::client::Event),
@@ -38,8 +53,13 @@ pub enum NodeBehaviourEvent {
 impl From<SharedBehaviourEvent> for NodeBehaviourEvent {
     fn from(value: SharedBehaviourEvent) -> Self {
         match value {
+            SharedBehaviourEvent::Limits(e) => match e {},
+            SharedBehaviourEvent::Allowed(e) => match e {},
+            SharedBehaviourEvent::Blocked(e) => match e {},
             SharedBehaviourEvent::Gossip(e) => Self::from(e),
             SharedBehaviourEvent::Identify(e) => Self::from(e),
+            SharedBehaviourEvent::Kad(e) => Self::from(e),
+            SharedBehaviourEvent::Mdns(e) => Self::from(e),
             SharedBehaviourEvent::ReqRes(e) => Self::from(e),
             SharedBehaviourEvent::Stream(e) => Self::Stream(e),
         }
@@ -51,6 +71,16 @@ impl From<identify::Event> for NodeBehaviourEvent {
         Self::Identify(value)
     }
 }
+impl From<kad::Event> for NodeBehaviourEvent {
+    fn from(value: kad::Event) -> Self {
+        Self::Kad(value)
+    }
+}
+impl From<mdns::Event> for NodeBehaviourEvent {
+    fn from(value: mdns::Event) -> Self {
+        Self::Mdns(value)
+    }
+}
 impl From<request_response::Event<NetworkRequest, NetworkResponse>> for NodeBehaviourEvent {
     fn from(value: request_response::Event<NetworkRequest, NetworkResponse>) -> Self {
         Self::ReqRes(value)
@@ -73,7 +103,7 @@ impl From<rendezvous::server::Event> for NodeBehaviourEvent {
 }
 
 impl SharedBehaviour {
-    pub fn new(keys: Keypair) -> Self {
+    pub fn new(keys: Keypair, config: &NodeConfig) -> Self {
         let message_id_fn = |message: &gossipsub::Message| {
             let mut s = DefaultHasher::new();
             message.data.hash(&mut s);
@@ -84,6 +114,16 @@ impl SharedBehaviour {
             keys.public(),
         ));
 
+        let peer_id = keys.public().to_peer_id();
+        let mut kad_config = kad::Config::new(KAD_PROTOCOL);
+        kad_config.set_query_timeout(KAD_QUERY_TIMEOUT);
+        let kad = Kademlia::with_config(peer_id, MemoryStore::new(peer_id), kad_config);
+
+        let mdns = Toggle::from(config.mdns.then(|| {
+            mdns::tokio::Behaviour::new(mdns::Config::default(), peer_id)
+                .expect("failed to build mdns behaviour")
+        }));
+
         let gossip_config = gossipsub::ConfigBuilder::default()
             .message_id_fn(message_id_fn)
             .build()
@@ -101,10 +141,30 @@ impl SharedBehaviour {
                 libp2p::request_response::Config::default(),
             );
 
+        let limits = connection_limits::Behaviour::new(config.connection_limits());
+
+        let allowed = Toggle::from((!config.allowed_peers.is_empty()).then(|| {
+            let mut allowed = allow_block_list::Behaviour::<AllowedPeers>::default();
+            for peer in config.allowed_peers.iter() {
+                allowed.allow_peer(*peer);
+            }
+            allowed
+        }));
+
+        let mut blocked = allow_block_list::Behaviour::<BlockedPeers>::default();
+        for peer in config.denied_peers.iter() {
+            blocked.block_peer(*peer);
+        }
+
         let stream = libp2p_stream::Behaviour::new();
         Self {
+            limits,
+            allowed,
+            blocked,
             gossip,
             identify,
+            kad,
+            mdns,
             req_res,
             stream,
         }
@@ -115,7 +175,7 @@ impl SharedBehaviour {
 pub trait NodeNetworkBehaviour:
     AsRef<SharedBehaviour> + AsMut<SharedBehaviour> + NetworkBehaviour
 {
-    fn new(keys: Keypair) -> Self
+    fn new(keys: Keypair, config: &NodeConfig) -> Self
     where
         Self: Sized;
 }
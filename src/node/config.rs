@@ -0,0 +1,124 @@
+use super::transport::TransportKind;
+use crate::blockchain::chain::DEFAULT_CHAIN_ID;
+use libp2p::{connection_limits::ConnectionLimits, Multiaddr, PeerId};
+use std::{path::PathBuf, time::Duration};
+
+/// Configuration shared by every node type
+#[derive(Debug, Clone)]
+pub struct NodeConfig {
+    /// Peers advertising a different chain id are disconnected from
+    pub chain_id: String,
+    /// Discover & dial peers on the local network with mDNS
+    pub mdns: bool,
+    /// Transports the swarm is built with, only addresses using one of these can be listened on
+    /// or dialed
+    pub transports: Vec<TransportKind>,
+    /// Most connections other peers may establish with us
+    pub max_inbound: Option<u32>,
+    /// Most connections we may establish with other peers
+    pub max_outbound: Option<u32>,
+    /// Most connections, in either direction, to a single peer
+    pub max_per_peer: Option<u32>,
+    /// How long a connection without any active streams is kept open
+    pub idle_timeout: Duration,
+    /// When not empty, connections to any other peer are denied
+    pub allowed_peers: Vec<PeerId>,
+    /// Connections to these peers are always denied
+    pub denied_peers: Vec<PeerId>,
+    /// How long a misbehaving peer is banned for
+    pub ban_duration: Duration,
+    /// Where the address book & session receipts are kept, nothing is persisted when unset
+    pub data_dir: Option<PathBuf>,
+}
+
+impl Default for NodeConfig {
+    fn default() -> Self {
+        Self {
+            chain_id: DEFAULT_CHAIN_ID.to_string(),
+            mdns: false,
+            transports: vec![TransportKind::Quic],
+            max_inbound: Some(64),
+            max_outbound: Some(32),
+            max_per_peer: Some(2),
+            idle_timeout: Duration::from_secs(60 * 10),
+            allowed_peers: vec![],
+            denied_peers: vec![],
+            ban_duration: Duration::from_secs(60 * 60),
+            data_dir: None,
+        }
+    }
+}
+
+impl NodeConfig {
+    pub fn supports(&self, addr: &Multiaddr) -> bool {
+        TransportKind::of(addr).is_some_and(|kind| self.transports.contains(&kind))
+    }
+
+    pub fn connection_limits(&self) -> ConnectionLimits {
+        ConnectionLimits::default()
+            .with_max_established_incoming(self.max_inbound)
+            .with_max_established_outgoing(self.max_outbound)
+            .with_max_established_per_peer(self.max_per_peer)
+    }
+}
+
+/// Command line arguments for the connection related parts of a `NodeConfig`
+#[derive(clap::Args, Debug)]
+pub struct ConnectionArgs {
+    /// Network to join, peers on any other network are disconnected from
+    #[arg(long)]
+    chain_id: Option<String>,
+    /// Most connections other peers may establish with us
+    #[arg(long)]
+    max_inbound: Option<u32>,
+    /// Most connections we may establish with other peers
+    #[arg(long)]
+    max_outbound: Option<u32>,
+    /// Most connections to a single peer
+    #[arg(long)]
+    max_per_peer: Option<u32>,
+    /// Seconds an idle connection is kept open
+    #[arg(long)]
+    idle_timeout: Option<u64>,
+    /// Only connect to these peers, may be passed more than once
+    #[arg(long = "allow")]
+    allowed_peers: Vec<PeerId>,
+    /// Never connect to these peers, may be passed more than once
+    #[arg(long = "deny")]
+    denied_peers: Vec<PeerId>,
+    /// Seconds a misbehaving peer is banned for
+    #[arg(long)]
+    ban_duration: Option<u64>,
+    /// Directory known peers & session receipts are remembered in across restarts
+    #[arg(long)]
+    data_dir: Option<PathBuf>,
+}
+
+impl ConnectionArgs {
+    /// Overrides the parts of `config` that were passed
+    pub fn apply(self, config: &mut NodeConfig) {
+        if let Some(chain_id) = self.chain_id {
+            config.chain_id = chain_id;
+        }
+        if let Some(max) = self.max_inbound {
+            config.max_inbound = Some(max);
+        }
+        if let Some(max) = self.max_outbound {
+            config.max_outbound = Some(max);
+        }
+        if let Some(max) = self.max_per_peer {
+            config.max_per_peer = Some(max);
+        }
+        if let Some(secs) = self.idle_timeout {
+            config.idle_timeout = Duration::from_secs(secs);
+        }
+        if let Some(secs) = self.ban_duration {
+            config.ban_duration = Duration::from_secs(secs);
+        }
+        if let Some(dir) = self.data_dir {
+            config.data_dir = Some(dir);
+        }
+        config.allowed_peers.extend(self.allowed_peers);
+        config.denied_peers.extend(self.denied_peers);
+    }
+}
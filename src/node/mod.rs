@@ -1,33 +1,69 @@
+pub mod address_book;
+pub mod bans;
 pub mod behaviour;
+pub mod config;
+pub mod peers;
 pub mod rpc;
+pub mod transport;
 use crate::{
-    behaviour::gossip::NetworkTopic,
-    blockchain::chain::{init_blockchain, Blockchain},
+    behaviour::{
+        gossip::NetworkTopic,
+        req_res::{NetworkRequest, NetworkResponse},
+        status::{NodeRole, Status},
+        IDENTIFY_ID,
+    },
+    blockchain::{
+        block::Block,
+        chain::{genesis_hash, init_blockchain, validate_chain, Blockchain},
+        ledger::Ledger,
+    },
     util::OneOf,
     MainResult,
 };
+use address_book::AddressBook;
+use bans::BanList;
 use behaviour::{NodeBehaviourEvent, NodeNetworkBehaviour};
+use config::NodeConfig;
 use futures::StreamExt;
 use libp2p::{
-    gossipsub,
+    gossipsub, identify,
     identity::Keypair,
-    swarm::{NetworkBehaviour, Swarm, SwarmEvent},
-    PeerId,
+    mdns, request_response,
+    swarm::{dial_opts::DialOpts, NetworkBehaviour, Swarm, SwarmEvent},
+    Multiaddr, PeerId,
 };
+use peers::PeerTable;
 use seraphic::{
     socket::{self},
     thread::RpcListeningThread,
     ProcessRequestResult, RpcHandler, RpcRequestWrapper,
 };
-use std::{collections::HashMap, fmt::Debug, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    time::{Duration, Instant},
+};
 use tokio::net::ToSocketAddrs;
 
+/// How often expired bans are lifted & the address book is saved
+const HOUSEKEEPING_INTERVAL: Duration = Duration::from_secs(30);
+/// Most known peers dialed on startup
+const BOOTSTRAP_PEERS: usize = 8;
+
 pub struct Node<T: NodeType> {
     keys: Keypair,
     rpc_thread: RpcListeningThread,
     blockchain: Blockchain,
-    pub decryption_keys: HashMap<PeerId, String>,
-    pub encryption_keys: HashMap<PeerId, String>,
+    /// Outputs `blockchain` leaves unspent
+    ledger: Ledger,
+    config: NodeConfig,
+    bans: BanList,
+    peers: PeerTable,
+    address_book: AddressBook,
+    /// Known peers dialed on startup that have not yet connected or failed
+    bootstrapping: HashSet<PeerId>,
+    /// Dialed if every peer in `bootstrapping` fails
+    fallback: Option<Multiaddr>,
     pub swarm: Swarm<T::Behaviour>,
     pub inner: T,
 }
@@ -51,32 +87,202 @@ where
     SwarmEvent<<<T as NodeType>::Behaviour as NetworkBehaviour>::ToSwarm>:
         Into<SwarmEvent<NodeBehaviourEvent>>,
 {
-    pub fn create_peer_keypair(&mut self, peer: &PeerId) -> MainResult<Keypair> {
-        let secret = self
-            .keys
-            .derive_secret(&peer.to_bytes())
-            .ok_or(std::io::Error::other("Could not derive secret"))?;
-        Ok(Keypair::ed25519_from_bytes(secret)?)
+    /// Identity keys of this node, inference sessions are authenticated with them
+    pub fn keys(&self) -> &Keypair {
+        &self.keys
+    }
+
+    pub fn blockchain(&self) -> &Blockchain {
+        &self.blockchain
+    }
+
+    pub fn ledger(&self) -> &Ledger {
+        &self.ledger
     }
 
-    pub async fn try_from_keys(keys: Keypair, addr: impl ToSocketAddrs) -> MainResult<Self> {
-        let mut swarm = Self::swarm(keys.clone())?;
-        let inner = T::init_with_swarm(&mut swarm)?;
+    /// Appends a block we made to the chain once it validates & gossips the new chain
+    pub fn add_block(&mut self, block: Block) -> MainResult<()> {
+        let mut chain = self.blockchain.clone();
+        chain.push(block);
+        self.ledger = validate_chain(&chain)?;
+        self.blockchain = chain;
+        match self.swarm.behaviour_mut().as_mut().gossip.publish(
+            NetworkTopic::ChainUpdate.publish(),
+            serde_json::to_vec(&self.blockchain)?,
+        ) {
+            Ok(_) | Err(gossipsub::PublishError::Duplicate) => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub async fn try_from_keys(
+        keys: Keypair,
+        addr: impl ToSocketAddrs,
+        config: NodeConfig,
+        inner_config: T::Config,
+    ) -> MainResult<Self> {
+        let mut swarm = Self::swarm(keys.clone(), &config)?;
+        let inner = T::init_with_swarm(&mut swarm, inner_config)?;
         let blockchain = init_blockchain();
+        let ledger = validate_chain(&blockchain)?;
+        let address_book = AddressBook::load(config.data_dir.as_deref())?;
         Ok(Self {
             inner,
             swarm,
             blockchain,
-            encryption_keys: HashMap::new(),
-            decryption_keys: HashMap::new(),
+            ledger,
+            config,
+            bans: BanList::default(),
+            peers: PeerTable::default(),
+            address_book,
+            bootstrapping: HashSet::new(),
+            fallback: None,
             keys,
             rpc_thread: RpcListeningThread::new(addr).await?,
         })
     }
 
+    /// Listens on `addr` as long as its transport is configured
+    pub fn listen_on(&mut self, addr: Multiaddr) -> MainResult<()> {
+        if !self.config.supports(&addr) {
+            return Err(format!("{addr} does not use any of {:?}", self.config.transports).into());
+        }
+        self.swarm.listen_on(addr)?;
+        Ok(())
+    }
+
+    /// Dials `addr` as long as its transport is configured
+    pub fn dial(&mut self, addr: Multiaddr) -> MainResult<()> {
+        if !self.config.supports(&addr) {
+            return Err(format!("{addr} does not use any of {:?}", self.config.transports).into());
+        }
+        self.swarm.dial(addr)?;
+        Ok(())
+    }
+
+    /// Dials the most reliable peers in the address book, `fallback` is only dialed if none are
+    /// known or every one of them fails
+    pub fn bootstrap(&mut self, fallback: Option<Multiaddr>) -> MainResult<()> {
+        let local = *self.swarm.local_peer_id();
+        let known: Vec<(PeerId, Vec<Multiaddr>)> = self
+            .address_book
+            .best()
+            .into_iter()
+            .filter(|(peer, _)| **peer != local && !self.bans.is_banned(peer))
+            .map(|(peer, record)| {
+                let addresses = record
+                    .addresses
+                    .iter()
+                    .filter(|addr| self.config.supports(addr))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                (*peer, addresses)
+            })
+            .filter(|(_, addresses)| !addresses.is_empty())
+            .take(BOOTSTRAP_PEERS)
+            .collect();
+
+        for (peer, addresses) in known {
+            tracing::info!("dialing known peer {peer}");
+            if self
+                .swarm
+                .dial(DialOpts::peer_id(peer).addresses(addresses).build())
+                .is_ok()
+            {
+                self.bootstrapping.insert(peer);
+            }
+        }
+
+        match (self.bootstrapping.is_empty(), fallback) {
+            (true, Some(addr)) => self.dial(addr)?,
+            (false, fallback) => self.fallback = fallback,
+            (true, None) => {}
+        }
+        Ok(())
+    }
+
+    pub fn address_book(&self) -> &AddressBook {
+        &self.address_book
+    }
+
+    /// What we tell peers about ourselves upon connecting
+    pub fn status(&self) -> Status {
+        Status {
+            chain_id: self.config.chain_id.clone(),
+            genesis_hash: genesis_hash(),
+            protocol_version: IDENTIFY_ID.to_string(),
+            tip_height: self.blockchain.len().saturating_sub(1) as u64,
+            role: T::ROLE,
+        }
+    }
+
+    pub fn peers(&self) -> &PeerTable {
+        &self.peers
+    }
+
+    /// Adds `peer` to the peer table if it is on our network, otherwise disconnects from it
+    fn record_status(&mut self, peer: PeerId, status: Status) {
+        match self.status().check_compatible(&status) {
+            Ok(()) => {
+                tracing::info!(
+                    "{peer} is a {:?} at height {}",
+                    status.role,
+                    status.tip_height
+                );
+                self.address_book.record_role(peer, status.role);
+                self.peers.insert(peer, status);
+            }
+            Err(err) => {
+                tracing::warn!("disconnecting {peer}, it is on another network: {err}");
+                self.peers.remove(&peer);
+                self.address_book.remove(&peer);
+                let _ = self.swarm.disconnect_peer_id(peer);
+            }
+        }
+    }
+
+    /// Disconnects from `peer` & denies any connection with it for the configured ban duration
+    pub fn ban_peer(&mut self, peer: PeerId, reason: impl std::fmt::Display) {
+        tracing::warn!(
+            "banning {peer} for {:?}: {reason}",
+            self.config.ban_duration
+        );
+        self.bans.ban(peer, self.config.ban_duration);
+        self.swarm.behaviour_mut().as_mut().blocked.block_peer(peer);
+    }
+
+    pub fn is_banned(&self, peer: &PeerId) -> bool {
+        self.bans.is_banned(peer)
+    }
+
+    /// Lifts expired bans & persists the address book
+    fn housekeeping(&mut self) {
+        self.lift_expired_bans();
+        if let Err(err) = self.address_book.save() {
+            tracing::error!("failed to save address book: {err}");
+        }
+    }
+
+    /// Lifts expired bans, statically denied peers stay blocked
+    fn lift_expired_bans(&mut self) {
+        for peer in self.bans.expire(Instant::now()) {
+            if self.config.denied_peers.contains(&peer) {
+                continue;
+            }
+            tracing::info!("ban on {peer} expired");
+            self.swarm
+                .behaviour_mut()
+                .as_mut()
+                .blocked
+                .unblock_peer(peer);
+        }
+    }
+
     pub async fn main_loop(&mut self) -> MainResult<()> {
+        let mut housekeeping = tokio::time::interval(HOUSEKEEPING_INTERVAL);
         loop {
             tokio::select! {
+                _ = housekeeping.tick() => self.housekeeping(),
                 swarm_event = self.swarm.select_next_some() => {
                     tracing::warn!("swarm event: {swarm_event:#?}");
                     if let Some(event) = T::handle_swarm_event(self, swarm_event).await? {
@@ -104,26 +310,177 @@ where
         event: impl Into<SwarmEvent<NodeBehaviourEvent>>,
     ) -> MainResult<()> {
         match Into::<SwarmEvent<NodeBehaviourEvent>>::into(event) {
-            SwarmEvent::ConnectionEstablished { .. } => {}
+            // Only the first connection to a peer needs a handshake
+            SwarmEvent::ConnectionEstablished {
+                peer_id,
+                num_established,
+                endpoint,
+                ..
+            } if num_established.get() == 1 => {
+                let dialed = endpoint
+                    .is_dialer()
+                    .then(|| endpoint.get_remote_address().clone());
+                self.address_book.record_connected(peer_id, dialed);
+                if self.bootstrapping.remove(&peer_id) {
+                    self.bootstrapping.clear();
+                    self.fallback = None;
+                }
+                let status = self.status();
+                self.swarm
+                    .behaviour_mut()
+                    .as_mut()
+                    .req_res
+                    .send_request(&peer_id, NetworkRequest::Status(status));
+            }
+            SwarmEvent::ConnectionClosed {
+                peer_id,
+                num_established: 0,
+                ..
+            } => {
+                self.peers.remove(&peer_id);
+            }
+            SwarmEvent::OutgoingConnectionError {
+                peer_id: Some(peer_id),
+                error,
+                ..
+            } => {
+                tracing::warn!("failed to dial {peer_id}: {error}");
+                self.address_book.record_failure(&peer_id);
+                if self.bootstrapping.remove(&peer_id)
+                    && self.bootstrapping.is_empty()
+                    && self.swarm.connected_peers().next().is_none()
+                {
+                    if let Some(addr) = self.fallback.take() {
+                        tracing::warn!("no known peers could be reached, dialing {addr}");
+                        self.dial(addr)?;
+                    }
+                }
+            }
+            SwarmEvent::Behaviour(NodeBehaviourEvent::ReqRes(
+                request_response::Event::Message {
+                    peer,
+                    message:
+                        request_response::Message::Request {
+                            request: NetworkRequest::Status(status),
+                            channel,
+                            ..
+                        },
+                },
+            )) => {
+                let ours = self.status();
+                let _ = self
+                    .swarm
+                    .behaviour_mut()
+                    .as_mut()
+                    .req_res
+                    .send_response(channel, NetworkResponse::Status(ours));
+                self.record_status(peer, status);
+            }
+            SwarmEvent::Behaviour(NodeBehaviourEvent::ReqRes(
+                request_response::Event::Message {
+                    peer,
+                    message:
+                        request_response::Message::Response {
+                            response: NetworkResponse::Status(status),
+                            ..
+                        },
+                },
+            )) => {
+                self.record_status(peer, status);
+            }
+            // Listen addresses are advertised as external so that rendezvous registrations carry them
+            SwarmEvent::NewListenAddr { address, .. } => {
+                self.swarm.add_external_address(address);
+            }
+            SwarmEvent::Behaviour(NodeBehaviourEvent::Mdns(mdns::Event::Discovered(
+                discovered,
+            ))) => {
+                let mut by_peer: HashMap<PeerId, Vec<Multiaddr>> = HashMap::new();
+                for (peer, address) in discovered {
+                    by_peer.entry(peer).or_default().push(address);
+                }
+                for (peer, addresses) in by_peer {
+                    if self.swarm.is_connected(&peer) || self.bans.is_banned(&peer) {
+                        continue;
+                    }
+                    tracing::info!("dialing {peer} discovered by mdns");
+                    // A dial from an earlier discovery may still be pending
+                    if let Err(err) = self
+                        .swarm
+                        .dial(DialOpts::peer_id(peer).addresses(addresses).build())
+                    {
+                        tracing::warn!("failed to dial {peer} discovered by mdns: {err}");
+                    }
+                }
+            }
+            // Peers that don't speak our protocol, possibly found through mdns, are dropped
+            SwarmEvent::Behaviour(NodeBehaviourEvent::Identify(identify::Event::Received {
+                peer_id,
+                info,
+                ..
+            })) if info.protocol_version != IDENTIFY_ID => {
+                tracing::warn!(
+                    "disconnecting {peer_id}, it speaks {} rather than {IDENTIFY_ID}",
+                    info.protocol_version
+                );
+                let _ = self.swarm.disconnect_peer_id(peer_id);
+            }
+            SwarmEvent::Behaviour(NodeBehaviourEvent::Identify(identify::Event::Received {
+                peer_id,
+                info,
+                ..
+            })) => {
+                self.address_book
+                    .record_addresses(&peer_id, info.listen_addrs.clone());
+                let kad = &mut self.swarm.behaviour_mut().as_mut().kad;
+                for address in info.listen_addrs {
+                    kad.add_address(&peer_id, address);
+                }
+            }
             SwarmEvent::Behaviour(NodeBehaviourEvent::Gossip(
                 libp2p::gossipsub::Event::Subscribed { peer_id, topic },
             )) if peer_id != *self.swarm.local_peer_id()
                 && topic == NetworkTopic::ChainUpdate.publish() =>
             {
-                self.swarm.behaviour_mut().as_mut().gossip.publish(
+                match self.swarm.behaviour_mut().as_mut().gossip.publish(
                     topic,
                     serde_json::to_vec(&self.blockchain).expect("failed to serialized blockchain"),
-                )?;
+                ) {
+                    // An identical chain was already published, so peers have it
+                    Ok(_) | Err(gossipsub::PublishError::Duplicate) => {}
+                    Err(err) => return Err(err.into()),
+                }
             }
             SwarmEvent::Behaviour(NodeBehaviourEvent::Gossip(
                 libp2p::gossipsub::Event::Message {
-                    message: gossipsub::Message { topic, data, .. },
+                    propagation_source,
+                    message:
+                        gossipsub::Message {
+                            topic,
+                            data,
+                            source,
+                            ..
+                        },
                     ..
                 },
             )) if topic == NetworkTopic::ChainUpdate.publish() => {
-                let chain: Blockchain = serde_json::from_slice(&data)?;
+                let sender = source.unwrap_or(propagation_source);
+                let chain: Blockchain = match serde_json::from_slice(&data) {
+                    Ok(chain) => chain,
+                    Err(err) => {
+                        self.ban_peer(sender, format!("sent an undecodable chain: {err}"));
+                        return Ok(());
+                    }
+                };
+                let ledger = match validate_chain(&chain) {
+                    Ok(ledger) => ledger,
+                    Err(err) => {
+                        self.ban_peer(sender, format!("sent an invalid chain: {err}"));
+                        return Ok(());
+                    }
+                };
 
-                if self.replace_chain(chain) {
+                if self.replace_chain(chain, ledger) {
                     tracing::warn!("replaced chain");
                 } else {
                     tracing::warn!("did not replace chain");
@@ -139,24 +496,23 @@ where
         Ok(())
     }
 
-    /// Put chain validation logic here
-    fn replace_chain(&mut self, potential_new_chain: Blockchain) -> bool {
+    /// Chains should be validated before being passed here
+    fn replace_chain(&mut self, potential_new_chain: Blockchain, ledger: Ledger) -> bool {
         if self.blockchain.len() < potential_new_chain.len() {
             self.blockchain = potential_new_chain;
+            self.ledger = ledger;
             return true;
         }
         false
     }
 
-    fn swarm(keys: Keypair) -> MainResult<Swarm<T::Behaviour>> {
+    fn swarm(keys: Keypair, config: &NodeConfig) -> MainResult<Swarm<T::Behaviour>> {
         let mut swarm = libp2p::SwarmBuilder::with_existing_identity(keys)
             .with_tokio()
-            .with_quic()
+            .with_other_transport(|key| transport::build(key, &config.transports))?
             .with_dns()?
-            .with_behaviour(|key| T::Behaviour::new(key.clone()))?
-            .with_swarm_config(|cfg| {
-                cfg.with_idle_connection_timeout(Duration::from_secs(u64::MAX))
-            })
+            .with_behaviour(|key| T::Behaviour::new(key.clone(), config))?
+            .with_swarm_config(|cfg| cfg.with_idle_connection_timeout(config.idle_timeout))
             .build();
         swarm
             .behaviour_mut()
@@ -174,9 +530,16 @@ pub trait NodeType: Debug {
     type Behaviour: NodeNetworkBehaviour;
     type Event: NodeTypeEvent;
     type RpcRequest: RpcRequestWrapper;
+    /// Anything particular to a node type that needs to be known at initialization
+    type Config: Debug;
+    /// Advertised to peers in our status
+    const ROLE: NodeRole;
     /// Where any logic particular to the initialization of a swarm can be implemented
     /// (Particular gossip topics, etc..)
-    fn init_with_swarm(swarm: &mut Swarm<Self::Behaviour>) -> MainResult<Self>
+    fn init_with_swarm(
+        swarm: &mut Swarm<Self::Behaviour>,
+        config: Self::Config,
+    ) -> MainResult<Self>
     where
         Self: Sized;
 
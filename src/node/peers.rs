@@ -0,0 +1,49 @@
+use crate::behaviour::status::Status;
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Connected peers that are on our network, along with the status each last sent us
+#[derive(Debug, Default)]
+pub struct PeerTable {
+    peers: HashMap<PeerId, Status>,
+}
+
+/// A single row of the peer table, as returned over rpc
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerEntry {
+    pub peer: PeerId,
+    pub status: Status,
+}
+
+impl PeerTable {
+    pub fn insert(&mut self, peer: PeerId, status: Status) {
+        self.peers.insert(peer, status);
+    }
+
+    pub fn remove(&mut self, peer: &PeerId) -> Option<Status> {
+        self.peers.remove(peer)
+    }
+
+    pub fn get(&self, peer: &PeerId) -> Option<&Status> {
+        self.peers.get(peer)
+    }
+
+    pub fn len(&self) -> usize {
+        self.peers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.peers.is_empty()
+    }
+
+    pub fn entries(&self) -> Vec<PeerEntry> {
+        self.peers
+            .iter()
+            .map(|(peer, status)| PeerEntry {
+                peer: *peer,
+                status: status.clone(),
+            })
+            .collect()
+    }
+}
@@ -1,3 +1,4 @@
+use crate::node::peers::PeerEntry;
 use seraphic::{RpcNamespace, RpcRequest, RpcRequestWrapper};
 use serde::{Deserialize, Serialize};
 
@@ -11,6 +12,7 @@ pub enum Namespace {
 pub enum RequestWrapper {
     PeerCount(GetPeerCountRequest),
     GetBalance(GetBalanceRequest),
+    Peers(GetPeersRequest),
 }
 
 #[derive(RpcRequest, Debug, Clone, Serialize, Deserialize)]
@@ -22,6 +24,16 @@ pub struct GetPeerCountResponse {
     pub count: u32,
 }
 
+/// Every peer in the peer table
+#[derive(RpcRequest, Debug, Clone, Serialize, Deserialize)]
+#[rpc_request(namespace = "Namespace:net")]
+pub struct GetPeersRequest;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetPeersResponse {
+    pub peers: Vec<PeerEntry>,
+}
+
 #[derive(RpcRequest, Debug, Clone, Serialize, Deserialize)]
 #[rpc_request(namespace = "Namespace:chain")]
 pub struct GetBalanceRequest {
@@ -1,12 +1,6 @@
 pub mod messages;
 use super::*;
-use crate::{
-    blockchain::transaction::{mint::Mint, transfer::Transfer, UTXO},
-    util::{
-        map_vec::{Contains, MapVec},
-        PublicKeyBytes,
-    },
-};
+use crate::util::PublicKeyBytes;
 pub use messages::*;
 use seraphic::ProcessRequestResult;
 
@@ -50,33 +44,21 @@ where
                 let json = serde_json::to_value(response)?;
                 Ok(Ok(json))
             }
+            RequestWrapper::Peers(_) => {
+                let response = GetPeersResponse {
+                    peers: self.peers.entries(),
+                };
+                let json = serde_json::to_value(response)?;
+                Ok(Ok(json))
+            }
             RequestWrapper::GetBalance(_get_bal) => {
                 let my_pub_key = PublicKeyBytes::from(self.keys.public());
 
                 // should evevnutally use the given address, but for now will return local addr
-                if let Some(top_block) = self.blockchain.peek() {
-                    let transfers: &MapVec<String, Transfer> = (*top_block).get_ref();
-                    let mut quantity = transfers.iter_vals().fold(0., |mut sum, t| {
-                        let utxos: &MapVec<String, UTXO> = t.get_ref();
-                        for v in utxos.iter_vals() {
-                            let key: &PublicKeyBytes = v.get_ref();
-                            if *key == my_pub_key {
-                                sum += v.amount();
-                            }
-                        }
-                        sum
-                    });
-                    let mint: &Mint = (*top_block).get_ref();
-                    let utxos: &MapVec<PublicKeyBytes, UTXO> = mint.get_ref();
-                    quantity += utxos
-                        .get(&my_pub_key)
-                        .and_then(|out| Some(out.amount()))
-                        .unwrap_or(&0.0);
-                    let response = GetBalanceResponse { quantity };
-                    let json = serde_json::to_value(response)?;
-                    return Ok(Ok(json));
-                }
-                Ok(Err(socket::Error::new_empty("1", "Empty chain")))
+                let quantity = self.ledger.balance(&my_pub_key);
+                let response = GetBalanceResponse { quantity };
+                let json = serde_json::to_value(response)?;
+                Ok(Ok(json))
             }
         }
     }
@@ -0,0 +1,116 @@
+use libp2p::{
+    core::{
+        muxing::StreamMuxerBox,
+        transport::{Boxed, OptionalTransport},
+        upgrade::Version,
+    },
+    identity::Keypair,
+    multiaddr::Protocol,
+    noise, quic, tcp, websocket, yamux, Multiaddr, PeerId, Transport,
+};
+
+use crate::{
+    blockchain::chain::{
+        BOOT_NODE_LISTEN_ADDR, BOOT_NODE_LISTEN_ADDR_TCP, BOOT_NODE_LISTEN_ADDR_WS,
+        BOOT_NODE_LOCAL_ADDR, BOOT_NODE_LOCAL_ADDR_TCP, BOOT_NODE_LOCAL_ADDR_WS,
+    },
+    MainResult,
+};
+
+/// Transports a node may be configured to listen & dial on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, clap::ValueEnum)]
+pub enum TransportKind {
+    Quic,
+    /// TCP secured with noise & multiplexed with yamux
+    Tcp,
+    /// Websockets over TCP, secured & multiplexed the same as `Tcp`
+    Websocket,
+}
+
+impl TransportKind {
+    /// The transport an address would be dialed or listened on with
+    pub fn of(addr: &Multiaddr) -> Option<Self> {
+        let mut kind = None;
+        for protocol in addr.iter() {
+            match protocol {
+                Protocol::QuicV1 => return Some(Self::Quic),
+                Protocol::Ws(_) | Protocol::Wss(_) => return Some(Self::Websocket),
+                Protocol::Tcp(_) => kind = Some(Self::Tcp),
+                _ => {}
+            }
+        }
+        kind
+    }
+
+    /// Listens on every interface on an os assigned port
+    pub fn default_listen_addr(&self) -> Multiaddr {
+        match self {
+            Self::Quic => "/ip4/0.0.0.0/udp/0/quic-v1",
+            Self::Tcp => "/ip4/0.0.0.0/tcp/0",
+            Self::Websocket => "/ip4/0.0.0.0/tcp/0/ws",
+        }
+        .parse()
+        .expect("invalid default listen address")
+    }
+
+    /// Where the boot node listens when using this transport
+    pub fn boot_node_listen_addr(&self) -> Multiaddr {
+        match self {
+            Self::Quic => BOOT_NODE_LISTEN_ADDR,
+            Self::Tcp => BOOT_NODE_LISTEN_ADDR_TCP,
+            Self::Websocket => BOOT_NODE_LISTEN_ADDR_WS,
+        }
+        .parse()
+        .expect("invalid boot node listen address")
+    }
+
+    /// Where the boot node can be dialed locally when using this transport
+    pub fn boot_node_local_addr(&self) -> Multiaddr {
+        match self {
+            Self::Quic => BOOT_NODE_LOCAL_ADDR,
+            Self::Tcp => BOOT_NODE_LOCAL_ADDR_TCP,
+            Self::Websocket => BOOT_NODE_LOCAL_ADDR_WS,
+        }
+        .parse()
+        .expect("invalid boot node local address")
+    }
+}
+
+/// Builds a transport out of every configured kind
+pub fn build(
+    keys: &Keypair,
+    kinds: &[TransportKind],
+) -> MainResult<Boxed<(PeerId, StreamMuxerBox)>> {
+    let quic = match kinds.contains(&TransportKind::Quic) {
+        true => OptionalTransport::some(quic::tokio::Transport::new(quic::Config::new(keys))),
+        false => OptionalTransport::none(),
+    };
+
+    let tcp = match kinds.contains(&TransportKind::Tcp) {
+        true => OptionalTransport::some(
+            tcp::tokio::Transport::new(tcp::Config::default())
+                .upgrade(Version::V1Lazy)
+                .authenticate(noise::Config::new(keys)?)
+                .multiplex(yamux::Config::default()),
+        ),
+        false => OptionalTransport::none(),
+    };
+
+    let websocket = match kinds.contains(&TransportKind::Websocket) {
+        true => OptionalTransport::some(
+            websocket::WsConfig::new(tcp::tokio::Transport::new(tcp::Config::default()))
+                .upgrade(Version::V1Lazy)
+                .authenticate(noise::Config::new(keys)?)
+                .multiplex(yamux::Config::default()),
+        ),
+        false => OptionalTransport::none(),
+    };
+
+    Ok(quic
+        .map(|(peer, conn), _| (peer, StreamMuxerBox::new(conn)))
+        .or_transport(tcp.map(|(peer, muxer), _| (peer, StreamMuxerBox::new(muxer))))
+        .map(|either, _| either.into_inner())
+        .or_transport(websocket.map(|(peer, muxer), _| (peer, StreamMuxerBox::new(muxer))))
+        .map(|either, _| either.into_inner())
+        .boxed())
+}
@@ -13,6 +13,11 @@ pub enum OneOf<T, O> {
 pub fn now_timestamp_string() -> String {
     Utc::now().to_rfc2822()
 }
+
+/// Milliseconds since the unix epoch
+pub fn now_millis() -> u64 {
+    Utc::now().timestamp_millis().max(0) as u64
+}
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq, Eq, Hash)]
 pub struct PublicKeyBytes(Vec<u8>);
 
@@ -0,0 +1,40 @@
+use core::{behaviour::status::NodeRole, node::address_book::AddressBook};
+use libp2p::{Multiaddr, PeerId};
+
+#[test]
+fn best_peers_are_most_reliable() {
+    let mut book = AddressBook::default();
+    let reliable = PeerId::random();
+    let flaky = PeerId::random();
+    book.record_connected(reliable, None);
+    book.record_connected(reliable, None);
+    book.record_connected(flaky, None);
+    book.record_failure(&flaky);
+    book.record_failure(&flaky);
+    // failures are only recorded for peers we have connected to before
+    book.record_failure(&PeerId::random());
+
+    let best: Vec<PeerId> = book.best().into_iter().map(|(peer, _)| *peer).collect();
+    assert_eq!(best, vec![reliable, flaky]);
+}
+
+#[test]
+fn address_book_persists() {
+    let dir = std::env::temp_dir().join(format!("address_book_{}", PeerId::random()));
+    let peer = PeerId::random();
+    let addr: Multiaddr = "/ip4/127.0.0.1/udp/1234/quic-v1".parse().unwrap();
+
+    let mut book = AddressBook::load(Some(&dir)).unwrap();
+    assert!(book.is_empty());
+    book.record_connected(peer, Some(addr.clone()));
+    book.record_addresses(&peer, vec![addr.clone()]);
+    book.record_role(peer, NodeRole::Provider);
+    book.save().unwrap();
+
+    let loaded = AddressBook::load(Some(&dir)).unwrap();
+    let record = loaded.get(&peer).unwrap();
+    assert_eq!(record.addresses, vec![addr]);
+    assert_eq!(record.role, Some(NodeRole::Provider));
+    assert_eq!(record.successes, 1);
+    std::fs::remove_dir_all(dir).unwrap();
+}
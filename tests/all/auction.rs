@@ -0,0 +1,118 @@
+use core::behaviour::{
+    auction::{AuctionMode, AuctionRequest, AuctionTerms, BidCommitment, BidReveal, SignedBid},
+    AuctionId, ProvisionBid,
+};
+use libp2p::{identity::Keypair, PeerId};
+
+fn terms() -> AuctionTerms {
+    AuctionTerms {
+        auction: AuctionId(7),
+        model: "m".to_string(),
+        input_tokens: 12,
+        expected_output_tokens: 256,
+        max_price_per_token: 2.5,
+        deadline: 1_000,
+        mode: AuctionMode::Open,
+    }
+}
+
+#[test]
+fn auction_requests_are_signed_by_the_client() {
+    let keys = Keypair::generate_ed25519();
+    let request = AuctionRequest::new(terms(), &keys).unwrap();
+    assert_eq!(request.verify().unwrap(), keys.public().to_peer_id());
+
+    let json = serde_json::to_vec(&request).unwrap();
+    let decoded: AuctionRequest = serde_json::from_slice(&json).unwrap();
+    assert_eq!(decoded, request);
+    assert_eq!(decoded.verify().unwrap(), keys.public().to_peer_id());
+
+    let mut raised = request.clone();
+    raised.terms.max_price_per_token = 100.;
+    assert!(raised.verify().is_err());
+}
+
+#[test]
+fn bids_are_signed_by_the_provider_they_claim() {
+    let keys = Keypair::generate_ed25519();
+    let peer = keys.public().to_peer_id();
+    let bid = ProvisionBid::new(peer, 1.5)
+        .for_auction(AuctionId(7))
+        .expiring(2_000);
+    let signed = SignedBid::new(bid, &keys).unwrap();
+    let json = serde_json::to_vec(&signed).unwrap();
+    let decoded: SignedBid = serde_json::from_slice(&json).unwrap();
+    assert_eq!(decoded.verify().unwrap(), peer);
+    assert!(!decoded.bid.expired(2_000));
+    assert!(decoded.bid.expired(2_001));
+
+    let mut undercut = signed.clone();
+    undercut.bid.bid = 0.5;
+    assert!(undercut.verify().is_err());
+    let mut extended = signed.clone();
+    extended.bid.expires = 10_000;
+    assert!(extended.verify().is_err());
+
+    // Signed by one provider while claiming to be another
+    let impersonating = ProvisionBid::new(PeerId::random(), 1.5).for_auction(AuctionId(7));
+    assert!(SignedBid::new(impersonating, &keys)
+        .unwrap()
+        .verify()
+        .is_err());
+}
+
+#[test]
+fn sealed_bids_only_open_their_commitment() {
+    let keys = Keypair::generate_ed25519();
+    let bid = |price| {
+        let bid = ProvisionBid::new(keys.public().to_peer_id(), price).for_auction(AuctionId(7));
+        SignedBid::new(bid, &keys).unwrap()
+    };
+    let (commitment, reveal) = BidReveal::seal(bid(1.5)).unwrap();
+    let json = serde_json::to_vec(&commitment).unwrap();
+    let commitment: BidCommitment = serde_json::from_slice(&json).unwrap();
+    assert!(commitment.opened_by(&reveal));
+
+    // Committing again to the same bid gives a different commitment
+    let (other_commitment, other_reveal) = BidReveal::seal(bid(1.5)).unwrap();
+    assert!(!commitment.opened_by(&other_reveal));
+    assert!(!other_commitment.opened_by(&reveal));
+    let (_, undercut) = BidReveal::seal(bid(0.5)).unwrap();
+    assert!(!commitment.opened_by(&undercut));
+
+    let unauctioned = SignedBid::new(ProvisionBid::new(PeerId::random(), 1.), &keys).unwrap();
+    assert!(BidReveal::seal(unauctioned).is_err());
+}
+
+#[test]
+fn sealed_auctions_close_after_reveals() {
+    let mut terms = terms();
+    assert_eq!(terms.closes(), 1_000);
+    terms.mode = AuctionMode::Sealed {
+        reveal_deadline: 1_500,
+    };
+    assert!(terms.expired(1_001));
+    assert!(!terms.closed(1_500));
+    assert!(terms.closed(1_501));
+
+    let json = serde_json::to_string(&terms).unwrap();
+    assert!(json.contains(r#""mode":{"sealed":{"reveal_deadline":1500}}"#));
+    assert_eq!(serde_json::from_str::<AuctionTerms>(&json).unwrap(), terms);
+}
+
+#[test]
+fn auction_terms() {
+    let terms = terms();
+    assert_eq!(terms.total_tokens(), 268);
+    assert!(!terms.expired(1_000));
+    assert!(terms.expired(1_001));
+}
+
+#[test]
+fn auction_ids_parse_from_their_display() {
+    let id = AuctionId(0xa8ff57cc10e91169);
+    assert_eq!(id.to_string(), "a8ff57cc10e91169");
+    assert_eq!(id.to_string().parse::<AuctionId>().unwrap(), id);
+    assert_eq!("7".parse::<AuctionId>().unwrap(), AuctionId(7));
+    assert!("not hex".parse::<AuctionId>().is_err());
+}
@@ -0,0 +1,57 @@
+use core::{
+    blockchain::{
+        block::Block,
+        chain::{init_blockchain, validate_chain, Blockchain, BOOT_NODE_KEYPAIR},
+    },
+    node::bans::BanList,
+    util::hash::Hash,
+};
+use libp2p::{identity::Keypair, PeerId};
+use std::{
+    sync::LazyLock,
+    time::{Duration, Instant},
+};
+
+#[test]
+fn bans_expire() {
+    let mut bans = BanList::default();
+    let short = PeerId::random();
+    let long = PeerId::random();
+    assert!(bans.ban(short, Duration::ZERO));
+    assert!(bans.ban(long, Duration::from_secs(60)));
+    assert!(!bans.ban(long, Duration::ZERO));
+
+    assert_eq!(bans.expire(Instant::now()), vec![short]);
+    assert!(!bans.is_banned(&short));
+    // re-banning for less time does not shorten a ban
+    assert!(bans.is_banned(&long));
+}
+
+#[test]
+fn chain_validation() {
+    let chain = init_blockchain();
+    assert!(validate_chain(&chain).is_ok());
+
+    let k = BOOT_NODE_KEYPAIR;
+    let boot_keys = LazyLock::force(&k);
+    let genesis_hash = chain.iter_vals().next().unwrap().hash_ref().to_string();
+    let next = Block::new_unsigned(1, 0, genesis_hash.clone(), vec![], boot_keys.public())
+        .sign(boot_keys)
+        .unwrap();
+    let mut longer = chain.clone();
+    longer.push(next);
+    assert!(validate_chain(&longer).is_ok());
+
+    let unlinked = Block::new_unsigned(1, 0, String::from("nope"), vec![], boot_keys.public())
+        .sign(boot_keys)
+        .unwrap();
+    let mut bad_link = chain.clone();
+    bad_link.push(unlinked);
+    assert!(validate_chain(&bad_link).is_err());
+
+    let stranger = Keypair::generate_ed25519();
+    let forged = Block::new_unsigned(0, 0, String::new(), vec![], stranger.public())
+        .sign(&stranger)
+        .unwrap();
+    assert!(validate_chain(&Blockchain::from(vec![forged])).is_err());
+}
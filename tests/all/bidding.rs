@@ -0,0 +1,94 @@
+use core::behaviour::{
+    auction::{AuctionMode, AuctionTerms},
+    bidding::{
+        BidStrategy, BidStrategyKind, CandidateBid, CheapestAcceptable, Reputation, Weighted,
+    },
+    AuctionId, ProvisionBid,
+};
+use libp2p::PeerId;
+use std::time::Duration;
+
+fn terms() -> AuctionTerms {
+    AuctionTerms {
+        auction: AuctionId(1),
+        model: "m".to_string(),
+        input_tokens: 4,
+        expected_output_tokens: 16,
+        max_price_per_token: 5.,
+        deadline: 1_000,
+        mode: AuctionMode::Open,
+    }
+}
+
+fn candidate(price: f64, latency_ms: u64, reputation: f64) -> CandidateBid {
+    CandidateBid {
+        bid: ProvisionBid::new(PeerId::random(), price),
+        latency: Duration::from_millis(latency_ms),
+        reputation,
+    }
+}
+
+#[test]
+fn cheapest_acceptable_bid_wins() {
+    let candidates = [
+        candidate(4., 10, 0.5),
+        candidate(6., 10, 0.5),
+        candidate(2., 900, 0.5),
+        candidate(3., 10, 0.1),
+    ];
+    let strategy = CheapestAcceptable::default();
+    assert_eq!(strategy.choose(&candidates, &terms()), Some(&candidates[2]));
+
+    let picky = CheapestAcceptable {
+        max_latency: Some(Duration::from_millis(100)),
+        min_reputation: 0.4,
+    };
+    assert_eq!(picky.choose(&candidates, &terms()), Some(&candidates[0]));
+
+    // nothing within budget means nothing is chosen
+    assert_eq!(strategy.choose(&candidates[1..2], &terms()), None);
+}
+
+#[test]
+fn weighted_strategy_trades_price_for_quality() {
+    let cheap = candidate(2., 3_000, 0.2);
+    let mut fast = candidate(3., 50, 0.9);
+    fast.bid = fast.bid.with_throughput(40.);
+    let candidates = [cheap, fast];
+
+    assert_eq!(
+        BidStrategyKind::Cheapest
+            .strategy()
+            .choose(&candidates, &terms()),
+        Some(&candidates[0])
+    );
+    assert_eq!(
+        Weighted::default().choose(&candidates, &terms()),
+        Some(&candidates[1])
+    );
+}
+
+#[test]
+fn reputation_follows_session_outcomes() {
+    let (good, bad) = (PeerId::random(), PeerId::random());
+    let mut reputation = Reputation::default();
+    assert_eq!(reputation.score(&good), 0.5);
+
+    for _ in 0..3 {
+        reputation.record(good, true);
+        reputation.record(bad, false);
+    }
+    reputation.record(good, false);
+    assert_eq!(reputation.score(&good), 4. / 6.);
+    assert_eq!(reputation.score(&bad), 1. / 5.);
+}
+
+#[test]
+fn strategy_kind_is_sent_as_one_digit() {
+    for kind in [BidStrategyKind::Cheapest, BidStrategyKind::Weighted] {
+        let json = serde_json::to_string(&kind).unwrap();
+        assert_eq!(json.len(), 1);
+        assert_eq!(serde_json::from_str::<BidStrategyKind>(&json).unwrap(), kind);
+    }
+    assert!(serde_json::from_str::<BidStrategyKind>("7").is_err());
+}
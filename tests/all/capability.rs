@@ -0,0 +1,70 @@
+use core::behaviour::{
+    auction::{AuctionMode, AuctionTerms},
+    capability::{Capabilities, CapabilityAdvert, ProviderDirectory, ADVERT_TTL},
+    AuctionId,
+};
+use libp2p::{identity::Keypair, PeerId};
+
+fn capabilities(model: &str, expires: u64) -> Capabilities {
+    Capabilities {
+        models: vec![model.to_string()],
+        max_context: 1_024,
+        throughput: Some(40.),
+        capacity: 3,
+        price_range: (2., 6.),
+        expires,
+    }
+}
+
+fn terms(model: &str, input_tokens: u64, max_price_per_token: f64) -> AuctionTerms {
+    AuctionTerms {
+        auction: AuctionId(5),
+        model: model.to_string(),
+        input_tokens,
+        expected_output_tokens: 256,
+        max_price_per_token,
+        deadline: 1_000,
+        mode: AuctionMode::Open,
+    }
+}
+
+#[test]
+fn adverts_are_signed_by_the_provider() {
+    let keys = Keypair::generate_ed25519();
+    let advert = CapabilityAdvert::new(capabilities("m", 1_000), &keys).unwrap();
+    let json = serde_json::to_vec(&advert).unwrap();
+    let decoded: CapabilityAdvert = serde_json::from_slice(&json).unwrap();
+    assert_eq!(decoded.verify().unwrap(), keys.public().to_peer_id());
+
+    let mut inflated = decoded.clone();
+    inflated.capabilities.max_context = 1_000_000;
+    assert!(inflated.verify().is_err());
+}
+
+#[test]
+fn capabilities_suit_jobs_they_can_take_on() {
+    let advert = capabilities("m", 1_000);
+    assert!(advert.suits(&terms("m", 768, 2.)));
+    assert!(!advert.suits(&terms("other", 12, 2.)));
+    assert!(!advert.suits(&terms("m", 769, 2.)));
+    assert!(!advert.suits(&terms("m", 12, 1.5)));
+}
+
+#[test]
+fn directories_forget_expired_adverts() {
+    let mut directory = ProviderDirectory::default();
+    assert!(directory.anyone_suits(&terms("m", 12, 2.), 0));
+
+    let (short, long) = (PeerId::random(), PeerId::random());
+    directory.insert(short, capabilities("m", 100), 0);
+    // Adverts are trusted for `ADVERT_TTL` at most, whatever they claim
+    directory.insert(long, capabilities("other", u64::MAX), 0);
+    let ttl = ADVERT_TTL.as_millis() as u64;
+    assert_eq!(directory.providers(100).count(), 2);
+    assert!(directory.anyone_suits(&terms("m", 12, 2.), 100));
+
+    let live: Vec<_> = directory.providers(101).map(|(peer, _)| *peer).collect();
+    assert_eq!(live, vec![long]);
+    assert!(!directory.anyone_suits(&terms("m", 12, 2.), 101));
+    assert_eq!(directory.providers(ttl + 1).count(), 0);
+}
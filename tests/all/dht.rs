@@ -0,0 +1,50 @@
+use core::{
+    behaviour::dht::{model_key, ProviderRecords, PROVIDER_RECORD_TTL},
+    MODEL_ID_0, MODEL_ID_1,
+};
+use libp2p::PeerId;
+use std::{collections::HashSet, time::Instant};
+
+#[test]
+fn models_have_their_own_key() {
+    assert_eq!(model_key(MODEL_ID_0), model_key(MODEL_ID_0));
+    assert_ne!(model_key(MODEL_ID_0), model_key(MODEL_ID_1));
+    assert_eq!(model_key("m").as_ref(), b"/model/m");
+}
+
+#[test]
+fn providers_are_tracked_per_model() {
+    let mut records = ProviderRecords::default();
+    let (first, second) = (PeerId::random(), PeerId::random());
+    let now = Instant::now();
+    assert!(records.providers(MODEL_ID_0, now).is_empty());
+
+    records.found(MODEL_ID_0, [first, second], now);
+    records.found(MODEL_ID_1, [second], now);
+    assert_eq!(
+        records.providers(MODEL_ID_0, now),
+        HashSet::from([first, second])
+    );
+    assert_eq!(records.providers(MODEL_ID_1, now), HashSet::from([second]));
+
+    // a provider that disconnects is forgotten for every model
+    records.forget(&second);
+    assert_eq!(records.providers(MODEL_ID_0, now), HashSet::from([first]));
+    assert!(records.providers(MODEL_ID_1, now).is_empty());
+}
+
+#[test]
+fn providers_expire_unless_found_again() {
+    let mut records = ProviderRecords::default();
+    let (stale, fresh) = (PeerId::random(), PeerId::random());
+    let now = Instant::now();
+    records.found(MODEL_ID_0, [stale, fresh], now);
+
+    let later = now + PROVIDER_RECORD_TTL;
+    records.found(MODEL_ID_0, [fresh], later - PROVIDER_RECORD_TTL / 2);
+    assert_eq!(records.providers(MODEL_ID_0, later), HashSet::from([fresh]));
+    records.expire(later);
+    assert_eq!(records.providers(MODEL_ID_0, now), HashSet::from([fresh]));
+    records.expire(later + PROVIDER_RECORD_TTL);
+    assert!(records.providers(MODEL_ID_0, later).is_empty());
+}
@@ -0,0 +1,65 @@
+use core::behaviour::{
+    encryption::{PendingHandshake, SessionCipher, Side},
+    AuctionId,
+};
+use libp2p::identity::Keypair;
+
+fn agree() -> (SessionCipher, SessionCipher) {
+    let (client_keys, provider_keys) = (Keypair::generate_ed25519(), Keypair::generate_ed25519());
+    let client = PendingHandshake::new(&client_keys, Side::Client, AuctionId(1)).unwrap();
+    let provider = PendingHandshake::new(&provider_keys, Side::Provider, AuctionId(1)).unwrap();
+    let (to_provider, to_client) = (client.handshake().clone(), provider.handshake().clone());
+    (
+        client
+            .finish(&to_client, &provider_keys.public().to_peer_id())
+            .unwrap(),
+        provider
+            .finish(&to_provider, &client_keys.public().to_peer_id())
+            .unwrap(),
+    )
+}
+
+#[test]
+fn frames_decrypt_in_order_only() {
+    let (mut client, mut provider) = agree();
+    let first = client.encrypt(b"first").unwrap();
+    let second = client.encrypt(b"second").unwrap();
+    assert_ne!(first, b"first");
+
+    // out of order & replayed frames are rejected
+    assert!(provider.decrypt(&second).is_err());
+    let (mut client, mut provider) = agree();
+    let first = client.encrypt(b"first").unwrap();
+    assert_eq!(provider.decrypt(&first).unwrap(), b"first");
+    assert!(provider.decrypt(&first).is_err());
+
+    // each direction has its own key
+    let reply = provider.encrypt(b"reply").unwrap();
+    assert!(provider.decrypt(&reply).is_err());
+    assert_eq!(client.decrypt(&reply).unwrap(), b"reply");
+
+    let mut tampered = client.encrypt(b"next").unwrap();
+    tampered[0] ^= 1;
+    assert!(provider.decrypt(&tampered).is_err());
+}
+
+#[test]
+fn handshakes_are_bound_to_identities() {
+    let (client_keys, provider_keys) = (Keypair::generate_ed25519(), Keypair::generate_ed25519());
+    let client = PendingHandshake::new(&client_keys, Side::Client, AuctionId(1)).unwrap();
+    let provider = PendingHandshake::new(&provider_keys, Side::Provider, AuctionId(1)).unwrap();
+
+    let someone_else = Keypair::generate_ed25519().public().to_peer_id();
+    assert!(client.finish(provider.handshake(), &someone_else).is_err());
+}
+
+#[test]
+fn handshakes_are_bound_to_auctions() {
+    let (client_keys, provider_keys) = (Keypair::generate_ed25519(), Keypair::generate_ed25519());
+    let client = PendingHandshake::new(&client_keys, Side::Client, AuctionId(1)).unwrap();
+    let provider = PendingHandshake::new(&provider_keys, Side::Provider, AuctionId(2)).unwrap();
+    assert_eq!(client.handshake().auction(), AuctionId(1));
+
+    let provider_id = provider_keys.public().to_peer_id();
+    assert!(client.finish(provider.handshake(), &provider_id).is_err());
+}
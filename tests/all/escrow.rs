@@ -0,0 +1,269 @@
+use core::{
+    behaviour::{
+        receipt::{Receipt, SignedReceipt},
+        streaming::Usage,
+        AuctionId,
+    },
+    blockchain::{
+        block::Block,
+        chain::{genesis_hash, init_blockchain, validate_chain, Blockchain},
+        ledger::{output_id, Ledger},
+        transaction::{
+            escrow::{Escrow, EscrowLedger},
+            transfer::Transfer,
+        },
+    },
+    util::hash::Hash,
+};
+use libp2p::identity::Keypair;
+
+struct Parties {
+    client: Keypair,
+    provider: Keypair,
+}
+
+impl Parties {
+    fn new() -> Self {
+        Self {
+            client: Keypair::generate_ed25519(),
+            provider: Keypair::generate_ed25519(),
+        }
+    }
+
+    /// Locks 10 out of `inputs` for the provider's session in auction 7, timing out at height 5
+    fn lock(&self, inputs: Vec<String>) -> Escrow {
+        let mut lock = Escrow::lock(
+            self.client.public(),
+            self.provider.public(),
+            AuctionId(7),
+            10.,
+            inputs,
+            5,
+        );
+        lock.sign(&self.client).unwrap();
+        lock
+    }
+
+    /// A receipt for 6 tokens at 1 each, countersigned by the client if `countersigned`
+    fn receipt(&self, auction: AuctionId, countersigned: bool) -> SignedReceipt {
+        self.receipt_priced(auction, 1., countersigned)
+    }
+
+    /// A receipt for 6 tokens at `price_per_token`
+    fn receipt_priced(
+        &self,
+        auction: AuctionId,
+        price_per_token: f64,
+        countersigned: bool,
+    ) -> SignedReceipt {
+        let receipt = Receipt {
+            auction,
+            client: self.client.public().to_peer_id(),
+            provider: self.provider.public().to_peer_id(),
+            usage: Usage {
+                input_tokens: 2,
+                output_tokens: 4,
+            },
+            price_per_token,
+            output_hash: Receipt::hash_output("output"),
+        };
+        let mut signed = SignedReceipt::new(receipt, &self.provider).unwrap();
+        if countersigned {
+            signed.countersign(&self.client).unwrap();
+        }
+        signed
+    }
+
+    fn claim(&self, lock: &Escrow, receipt: SignedReceipt) -> Escrow {
+        let mut claim = Escrow::claim(self.provider.public(), lock.hash_ref(), receipt);
+        claim.sign(&self.provider).unwrap();
+        claim
+    }
+
+    fn refund(&self, lock: &Escrow) -> Escrow {
+        let mut refund = Escrow::refund(self.client.public(), lock.hash_ref());
+        refund.sign(&self.client).unwrap();
+        refund
+    }
+}
+
+#[test]
+fn providers_claim_escrow_with_a_countersigned_receipt() {
+    let parties = Parties::new();
+    let lock = parties.lock(vec![]);
+    let mut ledger = EscrowLedger::default();
+    assert!(ledger.apply(1, &lock).unwrap().is_empty());
+    assert!(ledger.is_open(lock.hash_ref()));
+
+    // the client has to agree with the receipt, & it has to be for the locked auction
+    let unsigned = parties.claim(&lock, parties.receipt(AuctionId(7), false));
+    assert!(ledger.apply(2, &unsigned).is_err());
+    let elsewhere = parties.claim(&lock, parties.receipt(AuctionId(8), true));
+    assert!(ledger.apply(2, &elsewhere).is_err());
+    // nobody else can claim it, nor can it be claimed once it has timed out
+    let mut stolen = Escrow::claim(
+        parties.client.public(),
+        lock.hash_ref(),
+        parties.receipt(AuctionId(7), true),
+    );
+    stolen.sign(&parties.client).unwrap();
+    assert!(ledger.apply(2, &stolen).is_err());
+    let claim = parties.claim(&lock, parties.receipt(AuctionId(7), true));
+    assert!(ledger.apply(5, &claim).is_err());
+
+    // the provider is paid what was metered & the rest goes back to the client
+    let released = ledger.apply(4, &claim).unwrap();
+    let amounts: Vec<f64> = released.iter().map(|utxo| *utxo.amount()).collect();
+    assert_eq!(amounts, vec![6., 4.]);
+    assert!(!ledger.is_open(lock.hash_ref()));
+    assert!(ledger.apply(4, &claim).is_err());
+    assert!(ledger.apply(6, &parties.refund(&lock)).is_err());
+}
+
+#[test]
+fn clients_are_refunded_after_the_timeout() {
+    let parties = Parties::new();
+    let lock = parties.lock(vec![]);
+    let mut ledger = EscrowLedger::default();
+    // locks have to leave the provider time to claim them
+    assert!(ledger.apply(5, &lock).is_err());
+    ledger.apply(1, &lock).unwrap();
+
+    let refund = parties.refund(&lock);
+    assert!(ledger.apply(4, &refund).is_err());
+    let released = ledger.apply(5, &refund).unwrap();
+    assert_eq!(*released[0].amount(), 10.);
+    let claim = parties.claim(&lock, parties.receipt(AuctionId(7), true));
+    assert!(ledger.apply(5, &claim).is_err());
+}
+
+/// A chain where the client mined block 1, returning the id of its reward
+fn funded_chain(parties: &Parties) -> (Blockchain, String) {
+    let mut chain: Blockchain = init_blockchain();
+    let block = Block::new_unsigned(1, 0, genesis_hash(), vec![], parties.client.public())
+        .sign(&parties.client)
+        .unwrap();
+    let reward = output_id(block.hash_ref(), 0);
+    chain.push(block);
+    (chain, reward)
+}
+
+/// `chain` with a block holding `escrows` & `transfers` on top
+fn extend(chain: &Blockchain, escrows: Vec<Escrow>, transfers: Vec<Transfer>) -> Blockchain {
+    let miner = Keypair::generate_ed25519();
+    let top = chain.peek().unwrap();
+    let block = Block::new_unsigned(
+        top.index() + 1,
+        0,
+        top.hash_ref().to_string(),
+        transfers,
+        miner.public(),
+    )
+    .with_escrows(escrows)
+    .sign(&miner)
+    .unwrap();
+    let mut chain = chain.clone();
+    chain.push(block);
+    chain
+}
+
+#[test]
+fn locks_spend_unspent_outputs_of_the_client() {
+    let parties = Parties::new();
+    let (chain, reward) = funded_chain(&parties);
+    let ledger = validate_chain(&chain).unwrap();
+    let client = parties.client.public().into();
+    let balance = ledger.balance(&client);
+
+    // funds have to come from outputs the client owns & cover what is locked
+    assert!(validate_chain(&extend(&chain, vec![parties.lock(vec![])], vec![])).is_err());
+    let mut unowned = Escrow::lock(
+        parties.provider.public(),
+        parties.provider.public(),
+        AuctionId(7),
+        10.,
+        vec![reward.clone()],
+        5,
+    );
+    unowned.sign(&parties.provider).unwrap();
+    assert!(validate_chain(&extend(&chain, vec![unowned], vec![])).is_err());
+
+    let lock = parties.lock(vec![reward.clone()]);
+    let locked = extend(&chain, vec![lock.clone()], vec![]);
+    let ledger = validate_chain(&locked).unwrap();
+    assert!(ledger.escrows().is_open(lock.hash_ref()));
+    assert_eq!(ledger.balance(&client), balance - 10.);
+
+    // the inputs are spent, so they can't be locked again
+    let again = parties.lock(vec![reward]);
+    assert!(validate_chain(&extend(&locked, vec![again], vec![])).is_err());
+}
+
+#[test]
+fn claimed_escrow_can_be_spent() {
+    let parties = Parties::new();
+    let (chain, reward) = funded_chain(&parties);
+    let lock = parties.lock(vec![reward]);
+    let chain = extend(&chain, vec![lock.clone()], vec![]);
+    let claim = parties.claim(&lock, parties.receipt(AuctionId(7), true));
+    let chain = extend(&chain, vec![claim.clone()], vec![]);
+    let mut ledger: Ledger = validate_chain(&chain).unwrap();
+    let provider = parties.provider.public().into();
+    assert_eq!(ledger.balance(&provider), 6.);
+
+    let payout = output_id(claim.hash_ref(), 0);
+    let receiver = Keypair::generate_ed25519();
+    let mut transfer = Transfer::new(
+        parties.provider.public(),
+        receiver.public(),
+        6.,
+        vec![payout],
+        None,
+    );
+    transfer.sign(&parties.provider).unwrap();
+    ledger.spend(&transfer).unwrap();
+    assert_eq!(ledger.balance(&provider), 0.);
+    let chain = extend(&chain, vec![], vec![transfer]);
+    let ledger = validate_chain(&chain).unwrap();
+    assert_eq!(ledger.balance(&receiver.public().into()), 6.);
+}
+
+#[test]
+fn chains_with_broken_escrow_rules_are_invalid() {
+    let parties = Parties::new();
+    let (chain, reward) = funded_chain(&parties);
+    let lock = parties.lock(vec![reward]);
+    let chain = extend(&chain, vec![lock.clone()], vec![]);
+    assert!(validate_chain(&chain).is_ok());
+
+    let extended = |escrow: &Escrow| validate_chain(&extend(&chain, vec![escrow.clone()], vec![]));
+    assert!(extended(&parties.refund(&lock)).is_err());
+    let claim = parties.claim(&lock, parties.receipt(AuctionId(7), true));
+    assert!(extended(&claim).is_ok());
+
+    // only the signer can sign an escrow, & unsigned escrows make the block invalid
+    let mut forged = Escrow::refund(parties.client.public(), lock.hash_ref());
+    assert!(forged.sign(&parties.provider).is_err());
+    assert!(extended(&forged).is_err());
+}
+
+#[test]
+fn claims_only_pay_out_what_a_receipt_costs() {
+    let parties = Parties::new();
+    let (chain, reward) = funded_chain(&parties);
+    let lock = parties.lock(vec![reward]);
+    let chain = extend(&chain, vec![lock.clone()], vec![]);
+    let claim_priced = |price| {
+        let claim = parties.claim(&lock, parties.receipt_priced(AuctionId(7), price, true));
+        validate_chain(&extend(&chain, vec![claim], vec![]))
+    };
+    // paying out less than nothing would mint funds for the client
+    assert!(claim_priced(-1000.).is_err());
+    assert!(claim_priced(f64::NAN).is_err());
+
+    let ledger = claim_priced(0.).unwrap();
+    assert_eq!(ledger.balance(&parties.provider.public().into()), 0.);
+    let before = validate_chain(&chain).unwrap();
+    let client = parties.client.public().into();
+    assert_eq!(ledger.balance(&client), before.balance(&client) + 10.);
+}
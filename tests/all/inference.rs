@@ -0,0 +1,82 @@
+use core::{
+    behaviour::streaming::InferenceParams,
+    inference::{
+        mock::MockBackend,
+        subprocess::SubprocessBackend,
+        throughput::{MeteredBackend, Throughput},
+        InferenceBackend, TokenStream,
+    },
+    MainResult,
+};
+use futures::{executor::block_on, StreamExt};
+use std::{sync::Arc, time::Duration};
+
+fn collect(tokens: TokenStream) -> Vec<MainResult<String>> {
+    block_on(tokens.collect())
+}
+
+#[test]
+fn mock_backend_is_deterministic() {
+    let params = InferenceParams::default();
+    let echo = MockBackend::default();
+    let tokens: Vec<String> = collect(echo.complete("m", "hello there", &params).unwrap())
+        .into_iter()
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(tokens, vec!["hello ", "there"]);
+
+    let canned = MockBackend::replying("always this");
+    let tokens: Vec<String> = collect(canned.complete("m", "anything", &params).unwrap())
+        .into_iter()
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(tokens, vec!["always ", "this"]);
+}
+
+#[test]
+fn subprocess_backend_streams_stdout() {
+    // the child process is driven by tokio, so these need its runtime rather than an executor
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let params = InferenceParams::default();
+
+    let upper = SubprocessBackend::shell("tr a-z A-Z; printf \" $INFERENCE_MODEL\"");
+    let tokens: Vec<String> = runtime.block_on(async {
+        let tokens = upper.complete("m", "hello  there\n", &params).unwrap();
+        tokens.map(Result::unwrap).collect().await
+    });
+    assert_eq!(tokens, vec!["HELLO ", " THERE\n", " m"]);
+
+    let failing = SubprocessBackend::shell("echo partial; exit 3");
+    let tokens: Vec<MainResult<String>> = runtime.block_on(async {
+        let tokens = failing.complete("m", "", &params).unwrap();
+        tokens.collect().await
+    });
+    assert_eq!(tokens.len(), 2);
+    assert_eq!(tokens[0].as_ref().unwrap(), "partial\n");
+    assert!(tokens[1].is_err());
+}
+
+#[test]
+fn throughput_averages_recent_completions() {
+    let throughput = Throughput::default();
+    assert_eq!(throughput.tokens_per_sec(), None);
+    throughput.record(0, Duration::from_secs(1));
+    assert_eq!(throughput.tokens_per_sec(), None);
+
+    throughput.record(100, Duration::from_secs(2));
+    assert_eq!(throughput.tokens_per_sec(), Some(50.));
+    throughput.record(150, Duration::from_secs(1));
+    assert_eq!(throughput.tokens_per_sec(), Some(80.));
+}
+
+#[test]
+fn metered_backend_measures_completions() {
+    let params = InferenceParams::default();
+    let throughput = Throughput::default();
+    let backend = MeteredBackend::new(Arc::new(MockBackend::default()), throughput.clone());
+
+    let tokens = backend.complete("m", "hello there", &params).unwrap();
+    assert_eq!(throughput.tokens_per_sec(), None);
+    assert_eq!(collect(tokens).len(), 2);
+    assert!(throughput.tokens_per_sec().is_some_and(|tps| tps > 0.));
+}
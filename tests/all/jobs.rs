@@ -0,0 +1,43 @@
+use core::inference::jobs::{Jobs, Load};
+use futures::{executor::block_on, FutureExt};
+
+#[test]
+fn jobs_wait_for_a_free_slot() {
+    let jobs = Jobs::new(1, 1);
+    let first = jobs.enqueue().unwrap();
+    let second = jobs.enqueue().unwrap();
+    assert!(jobs.enqueue().is_none());
+
+    let running = block_on(first.start());
+    assert_eq!(
+        jobs.load(),
+        Load {
+            active: 1,
+            queued: 1,
+            capacity: 1,
+            queue_size: 1
+        }
+    );
+    assert_eq!(jobs.load().utilization(), 2.);
+
+    // the queued job can only start once the running one is done
+    let mut waiting = Box::pin(second.start());
+    assert!(waiting.as_mut().now_or_never().is_none());
+    drop(running);
+    let running = waiting.now_or_never().unwrap();
+    assert_eq!((jobs.load().active, jobs.load().queued), (1, 0));
+    assert!(jobs.load().has_room());
+
+    drop(running);
+    assert_eq!(jobs.load().utilization(), 0.);
+}
+
+#[test]
+fn abandoned_jobs_give_up_their_place() {
+    let jobs = Jobs::new(1, 0);
+    let queued = jobs.enqueue().unwrap();
+    assert!(jobs.enqueue().is_none());
+    drop(queued);
+    assert_eq!((jobs.load().active, jobs.load().queued), (0, 0));
+    assert!(jobs.enqueue().is_some());
+}
@@ -1,2 +1,19 @@
+pub mod address_book;
+pub mod auction;
+pub mod bans;
+pub mod bidding;
+pub mod capability;
+pub mod dht;
+pub mod encryption;
+pub mod escrow;
 pub mod helpers;
+pub mod inference;
+pub mod jobs;
 pub mod map_vec;
+pub mod pricing;
+pub mod receipt;
+pub mod rendezvous;
+pub mod status;
+pub mod streaming;
+pub mod transfer;
+pub mod transport;
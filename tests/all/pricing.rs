@@ -0,0 +1,76 @@
+use core::{
+    behaviour::{
+        auction::{AuctionMode, AuctionTerms},
+        pricing::PricingPolicy,
+        AuctionId,
+    },
+    inference::jobs::Load,
+};
+
+fn terms(model: &str, input_tokens: u64) -> AuctionTerms {
+    AuctionTerms {
+        auction: AuctionId(3),
+        model: model.to_string(),
+        input_tokens,
+        expected_output_tokens: 16,
+        max_price_per_token: 10.,
+        deadline: 1_000,
+        mode: AuctionMode::Open,
+    }
+}
+
+fn queued(queued: usize) -> Load {
+    Load {
+        active: 1,
+        queued,
+        capacity: 1,
+        queue_size: 4,
+    }
+}
+
+#[test]
+fn prices_follow_the_policy() {
+    let policy: PricingPolicy = serde_json::from_str(
+        r#"{"default_rate": 2, "rates": {"big": 4}, "queue_markup": 0.5, "min_job_price": 80, "floor": 1.5}"#,
+    )
+    .unwrap();
+    policy.validate().unwrap();
+
+    assert_eq!(policy.price(&terms("big", 24), &queued(0)), 4.);
+    assert_eq!(policy.price(&terms("other", 24), &queued(0)), 2.);
+    // every queued session adds half the rate
+    assert_eq!(policy.price(&terms("big", 24), &queued(2)), 8.);
+    // small jobs are charged at least the minimum job price
+    assert_eq!(policy.price(&terms("other", 4), &queued(0)), 4.);
+
+    let cheap = PricingPolicy {
+        default_rate: 0.5,
+        ..policy
+    };
+    assert_eq!(cheap.price(&terms("other", 64), &queued(0)), 1.5);
+}
+
+#[test]
+fn default_policy_keeps_the_old_price() {
+    let policy: PricingPolicy = serde_json::from_str("{}").unwrap();
+    assert_eq!(policy, PricingPolicy::default());
+    assert_eq!(policy.price(&terms("m", 4), &queued(3)), 5.);
+}
+
+#[test]
+fn negative_prices_are_rejected() {
+    let policy: PricingPolicy = serde_json::from_str(r#"{"rates": {"m": -1}}"#).unwrap();
+    assert!(policy.validate().is_err());
+}
+
+#[test]
+fn price_ranges_span_the_served_models() {
+    let policy: PricingPolicy = serde_json::from_str(
+        r#"{"default_rate": 2, "rates": {"big": 4, "small": 0.5}, "queue_markup": 0.5, "floor": 1}"#,
+    )
+    .unwrap();
+    let models = ["big".to_string(), "small".to_string()];
+    assert_eq!(policy.range(&models, 2), (1., 8.));
+    assert_eq!(policy.range(&models[..1], 0), (4., 4.));
+    assert_eq!(policy.range(&[], 2), (1., 1.));
+}
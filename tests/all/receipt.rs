@@ -0,0 +1,69 @@
+use core::behaviour::{
+    receipt::{Receipt, Receipts, SignedReceipt},
+    streaming::Usage,
+    AuctionId,
+};
+use libp2p::{identity::Keypair, PeerId};
+
+fn receipt(client: &Keypair, provider: &Keypair) -> Receipt {
+    Receipt {
+        auction: AuctionId(3),
+        client: client.public().to_peer_id(),
+        provider: provider.public().to_peer_id(),
+        usage: Usage {
+            input_tokens: 4,
+            output_tokens: 6,
+        },
+        price_per_token: 0.5,
+        output_hash: Receipt::hash_output("some output"),
+    }
+}
+
+#[test]
+fn receipts_are_signed_by_both_sides() {
+    let (client, provider) = (Keypair::generate_ed25519(), Keypair::generate_ed25519());
+    let mut signed = SignedReceipt::new(receipt(&client, &provider), &provider).unwrap();
+    signed.verify().unwrap();
+    assert!(!signed.countersigned());
+    assert_eq!(signed.receipt.cost(), 5.);
+    let digest = signed.receipt.digest().unwrap();
+    assert_eq!(digest.len(), 64);
+
+    // only the parties of the session can sign their part
+    assert!(SignedReceipt::new(receipt(&client, &provider), &client).is_err());
+    assert!(signed.clone().countersign(&provider).is_err());
+
+    signed.countersign(&client).unwrap();
+    assert!(signed.countersigned());
+    let json = serde_json::to_vec(&signed).unwrap();
+    let decoded: SignedReceipt = serde_json::from_slice(&json).unwrap();
+    decoded.verify().unwrap();
+    // countersigning doesn't change what payments refer to
+    assert_eq!(decoded.receipt.digest().unwrap(), digest);
+
+    let mut inflated = decoded.clone();
+    inflated.receipt.usage.output_tokens = 600;
+    assert!(inflated.verify().is_err());
+}
+
+#[test]
+fn receipts_persist() {
+    let dir = std::env::temp_dir().join(format!("receipts_{}", PeerId::random()));
+    let (client, provider) = (Keypair::generate_ed25519(), Keypair::generate_ed25519());
+    let mut signed = SignedReceipt::new(receipt(&client, &provider), &provider).unwrap();
+
+    let mut receipts = Receipts::load(Some(&dir)).unwrap();
+    assert!(receipts.is_empty());
+    receipts.insert(signed.clone()).unwrap();
+    // the countersigned receipt replaces the provider's
+    signed.countersign(&client).unwrap();
+    receipts.insert(signed.clone()).unwrap();
+    let mut forged = signed.clone();
+    forged.receipt.auction = AuctionId(4);
+    assert!(receipts.insert(forged).is_err());
+
+    let loaded = Receipts::load(Some(&dir)).unwrap();
+    assert_eq!(loaded.len(), 1);
+    assert_eq!(loaded.get(&AuctionId(3)), Some(&signed));
+    std::fs::remove_dir_all(dir).unwrap();
+}
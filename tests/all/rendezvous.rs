@@ -0,0 +1,23 @@
+use core::{
+    behaviour::rendezvous::RoleNamespace,
+    blockchain::chain::{boot_node_peer_id, BOOT_NODE_KEYPAIR},
+    MODEL_ID_0,
+};
+use libp2p::PeerId;
+use std::sync::LazyLock;
+
+#[test]
+fn boot_node_peer_id_matches_keypair() {
+    let k = BOOT_NODE_KEYPAIR;
+    let keys = LazyLock::force(&k);
+    assert_eq!(boot_node_peer_id(), PeerId::from(keys.public()));
+}
+
+#[test]
+fn role_namespaces() {
+    assert_eq!(
+        RoleNamespace::Providers(MODEL_ID_0).namespace().to_string(),
+        format!("providers/{MODEL_ID_0}")
+    );
+    assert_eq!(RoleNamespace::Miners.namespace().to_string(), "miners");
+}
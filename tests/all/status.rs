@@ -0,0 +1,58 @@
+use core::{
+    behaviour::{
+        status::{NodeRole, Status},
+        IDENTIFY_ID,
+    },
+    blockchain::chain::{genesis_hash, init_blockchain, DEFAULT_CHAIN_ID},
+    util::hash::Hash,
+};
+
+fn status() -> Status {
+    Status {
+        chain_id: DEFAULT_CHAIN_ID.to_string(),
+        genesis_hash: genesis_hash(),
+        protocol_version: IDENTIFY_ID.to_string(),
+        tip_height: 0,
+        role: NodeRole::Client,
+    }
+}
+
+#[test]
+fn genesis_is_deterministic() {
+    let first = init_blockchain();
+    let second = init_blockchain();
+    assert_eq!(
+        first.iter_vals().next().unwrap().hash_ref(),
+        second.iter_vals().next().unwrap().hash_ref()
+    );
+    assert_eq!(first.iter_vals().next().unwrap().hash_ref(), genesis_hash());
+}
+
+#[test]
+fn status_compatibility() {
+    let ours = status();
+    let provider = Status {
+        tip_height: 10,
+        role: NodeRole::Provider,
+        ..status()
+    };
+    assert!(ours.check_compatible(&provider).is_ok());
+
+    let other_chain = Status {
+        chain_id: "other".to_string(),
+        ..status()
+    };
+    assert!(ours.check_compatible(&other_chain).is_err());
+
+    let other_genesis = Status {
+        genesis_hash: "00".to_string(),
+        ..status()
+    };
+    assert!(ours.check_compatible(&other_genesis).is_err());
+
+    let other_version = Status {
+        protocol_version: "/id/2.0.0".to_string(),
+        ..status()
+    };
+    assert!(ours.check_compatible(&other_version).is_err());
+}
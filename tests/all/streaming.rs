@@ -0,0 +1,539 @@
+use core::{
+    behaviour::{
+        receipt::{Receipt, SignedReceipt},
+        streaming::{
+            read_frame, run_session, serve_session, write_frame, Completion, InferenceParams,
+            MessageStream, Progress, SessionOpen, StreamMessage, Usage, MAX_FRAME_SIZE,
+        },
+        AuctionId,
+    },
+    inference::{mock::MockBackend, InferenceBackend, TokenStream},
+    MainResult,
+};
+use futures::{
+    channel::oneshot, executor::block_on, io::Cursor, join, stream, AsyncRead, AsyncWrite,
+    FutureExt, StreamExt,
+};
+use libp2p::identity::Keypair;
+use std::{
+    collections::VecDeque,
+    io,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+/// Hands out a single byte per read, like a stream whose frames are split across packets
+struct Trickle(Cursor<Vec<u8>>);
+
+impl AsyncRead for Trickle {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let len = buf.len().min(1);
+        Pin::new(&mut self.0).poll_read(cx, &mut buf[..len])
+    }
+}
+
+/// Like `Trickle`, but every byte is only handed out after a pending read
+struct Stutter {
+    bytes: Trickle,
+    ready: bool,
+}
+
+impl AsyncRead for Stutter {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        self.ready = !self.ready;
+        if !self.ready {
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+        Pin::new(&mut self.bytes).poll_read(cx, buf)
+    }
+}
+
+/// One direction of a `Duplex`, remembering every byte that went through it
+#[derive(Default)]
+struct Pipe {
+    buf: VecDeque<u8>,
+    wire: Vec<u8>,
+    reader: Option<Waker>,
+    closed: bool,
+}
+
+/// In memory stream between both sides of a session
+struct Duplex {
+    reading: Arc<Mutex<Pipe>>,
+    writing: Arc<Mutex<Pipe>>,
+}
+
+impl Duplex {
+    fn pair() -> (Self, Self) {
+        let (a, b) = (Arc::default(), Arc::default());
+        (
+            Self {
+                reading: Arc::clone(&a),
+                writing: Arc::clone(&b),
+            },
+            Self {
+                reading: b,
+                writing: a,
+            },
+        )
+    }
+
+    /// Everything written to this side so far
+    fn wire(&self) -> Arc<Mutex<Pipe>> {
+        Arc::clone(&self.writing)
+    }
+
+    fn close_writing(&self) {
+        let mut pipe = self.writing.lock().unwrap();
+        pipe.closed = true;
+        if let Some(waker) = pipe.reader.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl Drop for Duplex {
+    fn drop(&mut self) {
+        self.close_writing();
+    }
+}
+
+impl AsyncRead for Duplex {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut pipe = self.reading.lock().unwrap();
+        if pipe.buf.is_empty() {
+            if pipe.closed {
+                return Poll::Ready(Ok(0));
+            }
+            pipe.reader = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+        let len = buf.len().min(pipe.buf.len());
+        for (byte, read) in buf.iter_mut().zip(pipe.buf.drain(..len)) {
+            *byte = read;
+        }
+        Poll::Ready(Ok(len))
+    }
+}
+
+impl AsyncWrite for Duplex {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut pipe = self.writing.lock().unwrap();
+        pipe.buf.extend(buf);
+        pipe.wire.extend_from_slice(buf);
+        if let Some(waker) = pipe.reader.take() {
+            waker.wake();
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.close_writing();
+        Poll::Ready(Ok(()))
+    }
+}
+
+fn open(model: &str) -> SessionOpen {
+    SessionOpen {
+        auction: AuctionId(1),
+        model: model.to_string(),
+        params: InferenceParams::default(),
+        price_per_token: 1.,
+        resume: None,
+    }
+}
+
+/// Streams the prompt's first word & then never finishes
+#[derive(Debug, Default)]
+struct Endless {
+    /// Set once the provider drops the stream, stopping the backend
+    stopped: Arc<Mutex<bool>>,
+}
+
+/// Flags `Endless::stopped` when dropped along with the backend's stream
+struct StopFlag(Arc<Mutex<bool>>);
+
+impl Drop for StopFlag {
+    fn drop(&mut self) {
+        *self.0.lock().unwrap() = true;
+    }
+}
+
+impl InferenceBackend for Endless {
+    fn complete(&self, _: &str, prompt: &str, _: &InferenceParams) -> MainResult<TokenStream> {
+        let first: MainResult<String> = Ok(prompt.split_inclusive(' ').next().unwrap().into());
+        let flag = StopFlag(self.stopped.clone());
+        // Holds the flag until the stream is dropped
+        let endless = stream::pending().map(move |token| {
+            let _ = &flag;
+            token
+        });
+        Ok(stream::iter([first]).chain(endless).boxed())
+    }
+}
+
+/// Dies after streaming the first `tokens` words of the prompt
+#[derive(Debug)]
+struct Dying {
+    tokens: usize,
+}
+
+impl InferenceBackend for Dying {
+    fn complete(&self, _: &str, prompt: &str, _: &InferenceParams) -> MainResult<TokenStream> {
+        let words = prompt.split_inclusive(' ').take(self.tokens);
+        let mut tokens: Vec<MainResult<String>> = words.map(|word| Ok(word.to_string())).collect();
+        tokens.push(Err("out of memory".into()));
+        Ok(stream::iter(tokens).boxed())
+    }
+}
+
+/// Both sides of a session over a `Duplex`, the provider serves "m" with `backend`
+struct Session {
+    client_keys: Keypair,
+    provider_keys: Keypair,
+    /// Who the client believes it is talking to
+    expected_provider: Keypair,
+    /// Auction the provider bid in, sessions for any other are refused
+    bid_in: AuctionId,
+    /// Price the provider bid per token
+    price: f64,
+    backend: Box<dyn InferenceBackend>,
+    /// What the client was streamed by earlier sessions, & by this one once it has run
+    progress: Progress,
+    /// Cancels the session once fired, never fired while it is None
+    cancel: Option<oneshot::Receiver<()>>,
+    client_end: Duplex,
+    provider_end: Duplex,
+}
+
+impl Session {
+    fn new(backend: impl InferenceBackend + 'static) -> Self {
+        let provider_keys = Keypair::generate_ed25519();
+        let (client_end, provider_end) = Duplex::pair();
+        Self {
+            client_keys: Keypair::generate_ed25519(),
+            expected_provider: provider_keys.clone(),
+            provider_keys,
+            bid_in: AuctionId(1),
+            price: 1.,
+            backend: Box::new(backend),
+            progress: Progress::default(),
+            cancel: None,
+            client_end,
+            provider_end,
+        }
+    }
+
+    fn run(
+        self,
+        model: &str,
+        prompt: &str,
+    ) -> (MainResult<Completion>, MainResult<Option<SignedReceipt>>) {
+        self.run_with(open(model), prompt).0
+    }
+
+    /// Runs the session opened with `open`, returning what the client was streamed as well
+    fn run_with(
+        mut self,
+        open: SessionOpen,
+        prompt: &str,
+    ) -> (
+        (MainResult<Completion>, MainResult<Option<SignedReceipt>>),
+        Progress,
+    ) {
+        let models = vec!["m".to_string()];
+        let provider = self.expected_provider.public().to_peer_id();
+        let client = self.client_keys.public().to_peer_id();
+        let (bid_in, price) = (self.bid_in, self.price);
+        let cancel = self.cancel.take().unwrap_or_else(|| oneshot::channel().1);
+        let outcome = block_on(async {
+            join!(
+                run_session(
+                    MessageStream::new(self.client_end),
+                    &self.client_keys,
+                    &provider,
+                    open,
+                    prompt.to_string(),
+                    &mut self.progress,
+                    cancel,
+                ),
+                serve_session(
+                    MessageStream::new(self.provider_end),
+                    &self.provider_keys,
+                    &client,
+                    |auction| (auction == bid_in).then_some(price),
+                    &models,
+                    self.backend.as_ref(),
+                )
+            )
+        });
+        (outcome, self.progress)
+    }
+}
+
+// the tokio test macro expects `core` to be the standard library's
+#[test]
+fn frames_round_trip() {
+    block_on(async {
+        let long = StreamMessage::Prompt("a".repeat(10_000));
+        let mut stream = MessageStream::new(Cursor::new(vec![]));
+        stream.send(&StreamMessage::Open(open("m"))).await.unwrap();
+        stream.send(&long).await.unwrap();
+        stream.send(&StreamMessage::Cancel).await.unwrap();
+
+        let written = stream.into_inner().into_inner();
+        let mut reader = Trickle(Cursor::new(written));
+        let mut received = vec![];
+        while let Some(message) = read_frame::<_, StreamMessage>(&mut reader).await.unwrap() {
+            received.push(message);
+        }
+        assert_eq!(
+            received,
+            vec![StreamMessage::Open(open("m")), long, StreamMessage::Cancel]
+        );
+    })
+}
+
+#[test]
+fn dropped_receives_keep_partial_frames() {
+    block_on(async {
+        let mut bytes = Cursor::new(vec![]);
+        write_frame(&mut bytes, &StreamMessage::Cancel)
+            .await
+            .unwrap();
+        write_frame(&mut bytes, &StreamMessage::Heartbeat)
+            .await
+            .unwrap();
+        let mut stream = MessageStream::new(Stutter {
+            bytes: Trickle(Cursor::new(bytes.into_inner())),
+            ready: true,
+        });
+
+        // Each poll reads at most one byte before the future is dropped
+        for _ in 0..3 {
+            assert!(stream.recv().now_or_never().is_none());
+        }
+        assert_eq!(stream.recv().await.unwrap(), Some(StreamMessage::Cancel));
+        assert_eq!(stream.recv().await.unwrap(), Some(StreamMessage::Heartbeat));
+        assert_eq!(stream.recv().await.unwrap(), None);
+    })
+}
+
+#[test]
+fn oversized_and_truncated_frames_error() {
+    block_on(async {
+        let mut oversized = ((MAX_FRAME_SIZE + 1) as u32).to_be_bytes().to_vec();
+        oversized.extend_from_slice(b"{}");
+        assert!(read_frame::<_, StreamMessage>(&mut Cursor::new(oversized))
+            .await
+            .is_err());
+
+        let mut bytes = Cursor::new(vec![]);
+        write_frame(&mut bytes, &StreamMessage::Cancel)
+            .await
+            .unwrap();
+        let mut truncated = bytes.into_inner();
+        truncated.pop();
+        assert!(read_frame::<_, StreamMessage>(&mut Cursor::new(truncated))
+            .await
+            .is_err());
+    })
+}
+
+#[test]
+fn provider_serves_a_session() {
+    let (completion, served) = Session::new(MockBackend::default()).run("m", "hello there");
+    let completion = completion.unwrap();
+    assert_eq!(completion.content, "hello there");
+    assert_eq!(
+        completion.usage(),
+        Usage {
+            input_tokens: 2,
+            output_tokens: 2
+        }
+    );
+    assert!(!completion.cancelled);
+    // Both sides keep the receipt the client countersigned
+    assert!(completion.receipt.countersigned());
+    assert_eq!(served.unwrap(), Some(completion.receipt));
+
+    let (completion, served) = Session::new(MockBackend::default()).run("other", "hello");
+    assert!(completion.is_err());
+    assert!(served.is_err());
+}
+
+#[test]
+fn sessions_are_encrypted_and_authenticated() {
+    let session = Session::new(MockBackend::replying("a secret reply"));
+    let (to_provider, to_client) = (session.client_end.wire(), session.provider_end.wire());
+    let (completion, served) = session.run("m", "a secret prompt");
+    served.unwrap();
+    assert_eq!(completion.unwrap().content, "a secret reply");
+
+    let seen = |wire: &Arc<Mutex<Pipe>>, text: &str| {
+        let wire = &wire.lock().unwrap().wire;
+        wire.windows(text.len())
+            .any(|window| window == text.as_bytes())
+    };
+    assert!(!seen(&to_provider, "secret"));
+    assert!(!seen(&to_provider, "\"m\""));
+    assert!(!seen(&to_client, "secret"));
+
+    // a client expecting someone else refuses the provider's handshake
+    let mut session = Session::new(MockBackend::default());
+    session.expected_provider = Keypair::generate_ed25519();
+    let (completion, served) = session.run("m", "hello");
+    assert!(completion.is_err());
+    assert!(served.is_err());
+}
+
+#[test]
+fn providers_only_serve_auctions_they_bid_in() {
+    let mut session = Session::new(MockBackend::default());
+    session.bid_in = AuctionId(2);
+    let (completion, served) = session.run("m", "hello");
+    assert!(completion.is_err());
+    assert!(served.unwrap_err().to_string().contains("auction"));
+}
+
+#[test]
+fn sessions_resume_where_a_dead_one_left_off() {
+    let prompt = "one two three four";
+    let ((completion, served), received) =
+        Session::new(Dying { tokens: 2 }).run_with(open("m"), prompt);
+    assert!(completion.is_err());
+    assert!(served.is_err());
+    assert_eq!(
+        received,
+        Progress {
+            content: "one two ".to_string(),
+            output_tokens: 2
+        }
+    );
+
+    // The provider taking over is only asked for the rest
+    let mut session = Session::new(MockBackend::default());
+    session.progress = received.clone();
+    let resumed = SessionOpen {
+        resume: Some(received.content),
+        ..open("m")
+    };
+    let ((completion, served), received) = session.run_with(resumed, prompt);
+    served.unwrap();
+    let completion = completion.unwrap();
+    assert_eq!(completion.content, prompt);
+    assert_eq!(completion.usage().output_tokens, 2);
+    assert_eq!(received.output_tokens, 4);
+    // Only the output of this session is billed
+    assert_eq!(
+        completion.receipt.receipt.output_hash,
+        Receipt::hash_output("three four")
+    );
+}
+
+#[test]
+fn cancelled_sessions_stop_the_backend_and_report_usage() {
+    let backend = Endless::default();
+    let stopped = backend.stopped.clone();
+    let mut session = Session::new(backend);
+    let (cancel, cancelled) = oneshot::channel();
+    session.cancel = Some(cancelled);
+    cancel.send(()).unwrap();
+
+    let ((completion, served), received) = session.run_with(open("m"), "never ending");
+    served.unwrap();
+    let completion = completion.unwrap();
+    assert!(completion.cancelled);
+    assert!(*stopped.lock().unwrap());
+    // Billed for exactly what was streamed before the provider stopped
+    assert_eq!(completion.usage().output_tokens, received.output_tokens);
+    assert_eq!(completion.content, received.content);
+    assert_eq!(completion.usage().input_tokens, 2);
+}
+
+#[test]
+fn providers_charge_what_they_bid() {
+    let mut session = Session::new(MockBackend::default());
+    session.price = 0.5;
+    let (completion, served) = session.run("m", "hello there");
+    let receipt = served.unwrap().unwrap();
+    assert_eq!(receipt.receipt.price_per_token, 0.5);
+    assert_eq!(receipt.receipt.cost(), 2.);
+    assert_eq!(completion.unwrap().receipt, receipt);
+
+    // A session opened below the bid isn't served
+    let mut session = Session::new(MockBackend::default());
+    session.price = 2.;
+    let (completion, served) = session.run("m", "hello");
+    assert!(completion.is_err());
+    assert!(served.unwrap_err().to_string().contains("per token"));
+}
+
+#[test]
+fn receipts_are_countersigned_after_a_late_cancel() {
+    let session = Session::new(MockBackend::default());
+    let (client_keys, provider_keys) = (session.client_keys, session.provider_keys);
+    let provider = provider_keys.public().to_peer_id();
+    let client = client_keys.public().to_peer_id();
+    let models = vec!["m".to_string()];
+    let cancels_late = async {
+        let mut stream = MessageStream::new(session.client_end);
+        stream
+            .handshake(&client_keys, &provider, AuctionId(1))
+            .await?;
+        stream.send(&StreamMessage::Open(open("m"))).await?;
+        stream
+            .send(&StreamMessage::Prompt("hello there".into()))
+            .await?;
+        let mut receipt = loop {
+            match stream.recv().await? {
+                Some(StreamMessage::Receipt(receipt)) => break receipt,
+                Some(_) => continue,
+                None => return Err("no receipt".into()),
+            }
+        };
+        // The cancel was already on its way when the output ended
+        stream.send(&StreamMessage::Cancel).await?;
+        receipt.countersign(&client_keys)?;
+        stream
+            .send(&StreamMessage::Countersigned(receipt.clone()))
+            .await?;
+        MainResult::Ok(receipt)
+    };
+    let (countersigned, served) = block_on(async {
+        join!(
+            cancels_late,
+            serve_session(
+                MessageStream::new(session.provider_end),
+                &provider_keys,
+                &client,
+                |_| Some(1.),
+                &models,
+                session.backend.as_ref(),
+            )
+        )
+    });
+    let countersigned = countersigned.unwrap();
+    assert!(countersigned.countersigned());
+    assert_eq!(served.unwrap(), Some(countersigned));
+}
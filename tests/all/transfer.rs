@@ -0,0 +1,110 @@
+use core::{
+    blockchain::{
+        block::Block,
+        chain::{find_transfer, genesis_hash, init_blockchain, validate_chain},
+        ledger::output_id,
+        transaction::transfer::Transfer,
+        ChainError,
+    },
+    util::hash::Hash,
+};
+use libp2p::identity::Keypair;
+
+#[test]
+fn only_the_sender_signs_a_transfer() {
+    let (sender, receiver) = (Keypair::generate_ed25519(), Keypair::generate_ed25519());
+    let mut transfer = Transfer::new(
+        sender.public(),
+        receiver.public(),
+        12.5,
+        vec![],
+        Some("receipt".to_string()),
+    );
+    assert!(transfer.valid());
+    assert!(!transfer.verify_signature());
+    assert!(matches!(
+        transfer.clone().sign(&receiver),
+        Err(ChainError::NotSender)
+    ));
+
+    transfer.sign(&sender).unwrap();
+    assert!(transfer.verify_signature());
+    assert_eq!(transfer.receipt(), Some("receipt"));
+    assert!(matches!(
+        transfer.sign(&sender),
+        Err(ChainError::SignatureExists)
+    ));
+
+    // the receipt a transfer settles is part of its hash
+    let other = Transfer::new(sender.public(), receiver.public(), 12.5, vec![], None);
+    assert_ne!(other.hash_ref(), transfer.hash_ref());
+}
+
+#[test]
+fn mined_transfers_are_found_in_the_chain() {
+    let (sender, miner) = (Keypair::generate_ed25519(), Keypair::generate_ed25519());
+    let mut transfer = Transfer::new(sender.public(), miner.public(), 3., vec![], None);
+    transfer.sign(&sender).unwrap();
+
+    let mut chain = init_blockchain();
+    assert_eq!(find_transfer(&chain, transfer.hash_ref()), None);
+    let block = Block::new_unsigned(1, 0, genesis_hash(), vec![transfer.clone()], miner.public());
+    chain.push(block.sign(&miner).unwrap());
+    assert_eq!(find_transfer(&chain, transfer.hash_ref()), Some(1));
+}
+
+#[test]
+fn transfers_spend_unspent_outputs_of_their_sender() {
+    let (sender, receiver) = (Keypair::generate_ed25519(), Keypair::generate_ed25519());
+    let mut chain = init_blockchain();
+    // the sender mines a block to have something to spend
+    let block = Block::new_unsigned(1, 0, genesis_hash(), vec![], sender.public());
+    let block = block.sign(&sender).unwrap();
+    let reward = output_id(block.hash_ref(), 0);
+    chain.push(block);
+    let ledger = validate_chain(&chain).unwrap();
+    let (sender_key, receiver_key) = (sender.public().into(), receiver.public().into());
+    let balance = ledger.balance(&sender_key);
+    assert!(balance > 3.);
+
+    let mut transfer = Transfer::new(
+        sender.public(),
+        receiver.public(),
+        3.,
+        vec![reward.clone()],
+        None,
+    )
+    .with_change(balance - 3.);
+    assert!(ledger.check(&transfer).is_err());
+    transfer.sign(&sender).unwrap();
+    assert!(ledger.check(&transfer).is_ok());
+
+    // more than the inputs hold, or outputs the sender doesn't own, can't be spent
+    let mut greedy = Transfer::new(
+        sender.public(),
+        receiver.public(),
+        balance + 1.,
+        vec![reward.clone()],
+        None,
+    );
+    greedy.sign(&sender).unwrap();
+    assert!(ledger.check(&greedy).is_err());
+    let mut theft = Transfer::new(receiver.public(), receiver.public(), 3., vec![reward], None);
+    theft.sign(&receiver).unwrap();
+    assert!(ledger.check(&theft).is_err());
+
+    let miner = Keypair::generate_ed25519();
+    let previous = chain.peek().unwrap().hash_ref().to_string();
+    let block = Block::new_unsigned(2, 0, previous, vec![transfer.clone()], miner.public());
+    let previous = block.hash_ref().to_string();
+    chain.push(block.sign(&miner).unwrap());
+    let ledger = validate_chain(&chain).unwrap();
+    assert_eq!(ledger.balance(&receiver_key), 3.);
+    assert_eq!(ledger.balance(&sender_key), balance - 3.);
+
+    // an output is only spent once
+    assert!(ledger.check(&transfer).is_err());
+    let block = Block::new_unsigned(3, 0, previous, vec![transfer], miner.public());
+    chain.push(block.sign(&miner).unwrap());
+    assert!(validate_chain(&chain).is_err());
+}
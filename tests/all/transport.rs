@@ -0,0 +1,25 @@
+use core::node::{config::NodeConfig, transport::TransportKind};
+use libp2p::Multiaddr;
+
+#[test]
+fn transport_kind_of_addr() {
+    let kind = |addr: &str| TransportKind::of(&addr.parse::<Multiaddr>().unwrap());
+    assert_eq!(
+        kind("/ip4/127.0.0.1/udp/1/quic-v1"),
+        Some(TransportKind::Quic)
+    );
+    assert_eq!(kind("/ip4/127.0.0.1/tcp/1"), Some(TransportKind::Tcp));
+    assert_eq!(
+        kind("/dns4/localhost/tcp/1/ws"),
+        Some(TransportKind::Websocket)
+    );
+    assert_eq!(kind("/ip4/127.0.0.1/udp/1"), None);
+}
+
+#[test]
+fn config_only_supports_configured_transports() {
+    let config = NodeConfig::default();
+    assert!(config.supports(&TransportKind::Quic.default_listen_addr()));
+    assert!(!config.supports(&TransportKind::Tcp.default_listen_addr()));
+    assert!(!config.supports(&TransportKind::Websocket.default_listen_addr()));
+}